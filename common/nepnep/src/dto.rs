@@ -53,6 +53,20 @@ impl Dir {
             _ => 0,
         }
     }
+
+    /// `cover_url` is the cover base url without trailing slash
+    pub fn to_manga_info(&self, source_id: i64, cover_url: &str) -> MangaInfo {
+        MangaInfo {
+            source_id,
+            title: self.s.clone(),
+            author: self.a.clone(),
+            genre: self.g.clone(),
+            status: Some(self.ss.clone()),
+            description: None,
+            path: format!("/manga/{}", self.i),
+            cover_url: format!("{}/{}.jpg", cover_url, self.i),
+        }
+    }
 }
 
 struct DateOrZeroVisitor;
@@ -77,6 +91,8 @@ impl<'de> de::Visitor<'de> for DateOrZeroVisitor {
     {
         if let Ok(dt) = NaiveDateTime::parse_from_str(v, "%Y-%m-%dT%H:%M:%S%z") {
             Ok(dt)
+        } else if let Ok(dt) = NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S") {
+            Ok(dt)
         } else {
             Err(E::invalid_value(Unexpected::Str(v), &self))
         }
@@ -90,18 +106,37 @@ where
     deserializer.deserialize_any(DateOrZeroVisitor)
 }
 
-#[allow(clippy::from_over_into)]
-impl Into<MangaInfo> for &Dir {
-    fn into(self) -> MangaInfo {
+/// Entry of the home page `vm.LatestJSON`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LatestChapter {
+    pub index_name: String,
+    pub series_name: String,
+    pub scan_status: Option<String>,
+    pub chapter: String,
+    /// Comma separated genres
+    pub genres: Option<String>,
+    #[serde(deserialize_with = "date_or_zero")]
+    pub date: NaiveDateTime,
+}
+
+impl LatestChapter {
+    pub fn to_manga_info(&self, source_id: i64, cover_url: &str) -> MangaInfo {
         MangaInfo {
-            source_id: 0,
-            title: self.s.clone(),
-            author: self.a.clone(),
-            genre: self.g.clone(),
-            status: Some(self.ss.clone()),
+            source_id,
+            title: self.series_name.clone(),
+            author: vec![],
+            genre: self
+                .genres
+                .iter()
+                .flat_map(|genres| genres.split(','))
+                .map(|genre| genre.trim().to_string())
+                .filter(|genre| !genre.is_empty())
+                .collect(),
+            status: self.scan_status.clone(),
             description: None,
-            path: format!("/manga/{}", self.i),
-            cover_url: format!("https://temp.compsci88.com/cover/{}.jpg", self.i),
+            path: format!("/manga/{}", self.index_name),
+            cover_url: format!("{}/{}.jpg", cover_url, self.index_name),
         }
     }
 }
@@ -109,8 +144,6 @@ impl Into<MangaInfo> for &Dir {
 #[derive(Debug, Clone, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DirChapter {
-    #[serde(skip)]
-    pub index_name: String,
     #[serde(rename(deserialize = "Chapter"))]
     pub chapter: String,
     #[serde(rename(deserialize = "Type"))]
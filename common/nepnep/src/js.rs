@@ -0,0 +1,340 @@
+//! Extract values assigned to javascript globals, e.g. `vm.Directory = [...];`
+
+use anyhow::{anyhow, bail, Result};
+use serde::de::DeserializeOwned;
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$'
+}
+
+/// Index right after the string literal starting at `start`
+fn skip_string(bytes: &[u8], start: usize) -> Option<usize> {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b if b == quote => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+/// Index right after the comment starting at `start`, if there is one
+fn skip_comment(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start + 1) {
+        Some(b'/') => Some(
+            bytes[start..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map(|end| start + end)
+                .unwrap_or(bytes.len()),
+        ),
+        Some(b'*') => Some(
+            bytes[start + 2..]
+                .windows(2)
+                .position(|w| w == b"*/")
+                .map(|end| start + 2 + end + 2)
+                .unwrap_or(bytes.len()),
+        ),
+        _ => None,
+    }
+}
+
+/// Index where the expression starting at `start` ends, brackets and string
+/// literals are balanced so values can span multiple lines
+fn scan_value(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => {
+                i = skip_string(bytes, i)?;
+                continue;
+            }
+            b'/' => {
+                if let Some(end) = skip_comment(bytes, i) {
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                    i = end;
+                    continue;
+                }
+            }
+            b'[' | b'{' | b'(' => depth += 1,
+            b']' | b'}' | b')' => {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+            b';' | b',' | b'\n' | b'\r' if depth == 0 => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    (depth == 0).then_some(bytes.len())
+}
+
+/// Every source expression assigned to `name` in `body`, in document order
+fn assignments<'a>(body: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let bytes = body.as_bytes();
+    body.match_indices(name).filter_map(move |(index, _)| {
+        if index > 0 && (is_ident(bytes[index - 1]) || bytes[index - 1] == b'.') {
+            return None;
+        }
+
+        let mut i = index + name.len();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'=') || matches!(bytes.get(i + 1), Some(b'=') | Some(b'>')) {
+            return None;
+        }
+
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        let end = scan_value(bytes, i)?;
+        let value = body[i..end].trim();
+        (!value.is_empty()).then_some(value)
+    })
+}
+
+/// Source of the first expression assigned to `name`
+pub fn extract_raw<'a>(body: &'a str, name: &'a str) -> Option<&'a str> {
+    assignments(body, name).next()
+}
+
+/// First value assigned to `name` that deserializes as `T`
+pub fn extract_json<T: DeserializeOwned>(body: &str, name: &str) -> Result<T> {
+    let mut error = None;
+    for value in assignments(body, name) {
+        match serde_json::from_str(value) {
+            Ok(value) => return Ok(value),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    match error {
+        Some(e) => Err(anyhow!("failed to parse {}: {}", name, e)),
+        None => Err(anyhow!("{} not found", name)),
+    }
+}
+
+/// Decode a javascript string literal
+pub fn parse_string(literal: &str) -> Result<String> {
+    let quote = match literal.chars().next() {
+        Some(quote @ ('"' | '\'' | '`')) if literal.len() > 1 && literal.ends_with(quote) => quote,
+        _ => bail!("{} is not a string literal", literal),
+    };
+    if quote == '"' {
+        return Ok(serde_json::from_str(literal)?);
+    }
+
+    let mut string = String::new();
+    let mut chars = literal[1..literal.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('r') => string.push('\r'),
+            Some('t') => string.push('\t'),
+            Some('0') => string.push('\0'),
+            Some(c) => string.push(c),
+            None => bail!("unterminated escape in {}", literal),
+        }
+    }
+
+    Ok(string)
+}
+
+/// Every string literal found in a javascript expression, comments are skipped
+pub fn string_literals(expression: &str) -> impl Iterator<Item = String> + '_ {
+    let bytes = expression.as_bytes();
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        while i < bytes.len() {
+            if matches!(bytes[i], b'"' | b'\'' | b'`') {
+                let start = i;
+                i = skip_string(bytes, start)?;
+                if let Ok(literal) = parse_string(&expression[start..i]) {
+                    return Some(literal);
+                }
+            } else if let Some(end) = (bytes[i] == b'/')
+                .then(|| skip_comment(bytes, i))
+                .flatten()
+            {
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+
+        None
+    })
+}
+
+/// First string literal assigned to `name`
+pub fn extract_string(body: &str, name: &str) -> Result<String> {
+    assignments(body, name)
+        .find_map(|value| parse_string(value).ok())
+        .ok_or_else(|| anyhow!("{} not found", name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_extract_nested_multiline() {
+        let body = r#"
+            <script>
+                vm.IndexName = "Kingdom";
+                vm.Chapters = [
+                    {"Chapter": "100010", "ChapterName": "a ]; tricky }; name"},
+                    {"Chapter": "100020", "ChapterName": null, "Nested": [[1, 2], {"a": [3]}]}
+                ];
+                vm.IndexNameLength = 7;
+            </script>
+        "#;
+
+        let chapters: Value = extract_json(body, "vm.Chapters").unwrap();
+        assert_eq!(chapters.as_array().unwrap().len(), 2);
+        assert_eq!(chapters[0]["ChapterName"], "a ]; tricky }; name");
+        assert_eq!(chapters[1]["Nested"][1]["a"][0], 3);
+
+        assert_eq!(extract_string(body, "vm.IndexName").unwrap(), "Kingdom");
+        assert_eq!(extract_raw(body, "vm.IndexNameLength"), Some("7"));
+    }
+
+    #[test]
+    fn test_extract_skips_comparisons_and_longer_names() {
+        let body = r#"
+            if (vm.CurPathName == "") {}
+            othervm.CurPathName = "wrong.example.org";
+            vm.CurPathNameBackup = "backup.example.org";
+            vm.CurPathName='official.lowee.us'
+        "#;
+
+        assert_eq!(
+            extract_string(body, "vm.CurPathName").unwrap(),
+            "official.lowee.us"
+        );
+    }
+
+    #[test]
+    fn test_extract_json_takes_first_valid_value() {
+        let body = r#"
+            vm.CurChapter = {"Chapter": 100010};
+            vm.CurChapter = {"Chapter": "100010"};
+        "#;
+
+        let value: std::collections::HashMap<String, String> =
+            extract_json(body, "vm.CurChapter").unwrap();
+        assert_eq!(value.get("Chapter").map(String::as_str), Some("100010"));
+        assert!(extract_json::<Value>(body, "vm.Directory").is_err());
+    }
+
+    #[test]
+    fn test_extract_function_and_comments() {
+        let body = r#"
+            vm.CoverURL = function(IndexName) {
+                // don't hard code this
+                return 'https://temp.compsci88.com/cover/' + IndexName + '.jpg';
+            };
+            vm.PageOne = "-page-1"; // reader's first page
+        "#;
+
+        let cover_url = extract_raw(body, "vm.CoverURL").unwrap();
+        assert!(cover_url.starts_with("function(IndexName)"));
+        assert!(cover_url.ends_with('}'));
+        assert_eq!(
+            string_literals(cover_url).collect::<Vec<_>>(),
+            vec!["https://temp.compsci88.com/cover/", ".jpg"]
+        );
+        assert_eq!(extract_string(body, "vm.PageOne").unwrap(), "-page-1");
+    }
+
+    #[test]
+    fn test_parse_string() {
+        assert_eq!(parse_string(r#""a\"bé""#).unwrap(), "a\"bé");
+        assert_eq!(parse_string(r"'it\'s\n'").unwrap(), "it's\n");
+        assert_eq!(parse_string("`plain`").unwrap(), "plain");
+        assert!(parse_string("'").is_err());
+        assert!(parse_string("42").is_err());
+    }
+
+    fn json_value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            any::<i64>().prop_map(Value::from),
+            ".*".prop_map(Value::String),
+        ];
+        leaf.prop_recursive(4, 32, 8, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+                prop::collection::hash_map(".*", inner, 0..8)
+                    .prop_map(|map| Value::Object(map.into_iter().collect())),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_extract_json_roundtrip(
+            value in json_value(),
+            pretty in any::<bool>(),
+            spacing in "[ \t\n]{0,3}",
+        ) {
+            let json = if pretty {
+                serde_json::to_string_pretty(&value).unwrap()
+            } else {
+                serde_json::to_string(&value).unwrap()
+            };
+            let body = format!(
+                "<script>vm.Before = 1;\nvm.Value{spacing}={spacing}{json};\nvm.After = \"x\";</script>"
+            );
+
+            let extracted: Value = extract_json(&body, "vm.Value").unwrap();
+            prop_assert_eq!(extracted, value);
+        }
+
+        #[test]
+        fn prop_extract_string_roundtrip(string in ".*", single_quote in any::<bool>()) {
+            let literal = if single_quote {
+                format!(
+                    "'{}'",
+                    string
+                        .replace('\\', "\\\\")
+                        .replace('\'', "\\'")
+                        .replace('\n', "\\n")
+                        .replace('\r', "\\r")
+                )
+            } else {
+                serde_json::to_string(&string).unwrap()
+            };
+            let body = format!("vm.Other = '';\nvm.Name = {literal};\n");
+
+            prop_assert_eq!(extract_string(&body, "vm.Name").unwrap(), string);
+        }
+
+        #[test]
+        fn prop_extract_never_panics(body in ".*", name in "vm\\.[A-Za-z]{1,8}") {
+            let _ = extract_raw(&body, &name);
+            let _ = extract_json::<Value>(&body, &name);
+            let _ = extract_string(&body, &name);
+        }
+    }
+}
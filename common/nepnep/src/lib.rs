@@ -1,6 +1,9 @@
 mod dto;
+mod js;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Result};
 use fancy_regex::Regex;
@@ -8,7 +11,7 @@ use scraper::{Html, Selector};
 use tanoshi_lib::prelude::{ChapterInfo, Input, InputType, MangaInfo, TriState};
 use networking::Agent;
 
-use crate::dto::{CurChapter, Dir, DirChapter};
+use crate::dto::{CurChapter, Dir, DirChapter, LatestChapter};
 
 use lazy_static::lazy_static;
 
@@ -203,6 +206,11 @@ lazy_static! {
         ],
         selection: None
     };
+    static ref COVER_URL_PREFERENCE: Input = Input::Text {
+        name: "Cover URL".to_string(),
+        state: None
+    };
+    static ref PREFERENCES: Vec<Input> = vec![COVER_URL_PREFERENCE.clone()];
     static ref FILTER_LIST: Vec<Input> = vec![
         KEYWORD_FILTER.clone(),
         GENRE_FILTER.clone(),
@@ -216,19 +224,152 @@ pub fn get_filter_list() -> Vec<Input> {
     FILTER_LIST.clone()
 }
 
+pub fn get_preferences() -> Vec<Input> {
+    PREFERENCES.clone()
+}
+
+/// Cover url override set in the extension preferences
+pub fn get_cover_url(preferences: &[Input]) -> Option<String> {
+    preferences.iter().find_map(|pref| match pref {
+        Input::Text {
+            name,
+            state: Some(state),
+        } if name == &COVER_URL_PREFERENCE.name() && !state.trim().is_empty() => {
+            Some(state.trim().to_string())
+        }
+        _ => None,
+    })
+}
+
+/// Used when neither the preference nor the search page provides a cover url
+const DEFAULT_COVER_URL: &str = "https://temp.compsci88.com/cover";
+
+fn parse_directory(html: &str) -> Result<Vec<Dir>> {
+    js::extract_json(html, "vm.Directory")
+}
+
+/// Find the cover base url used by the search page, either from the
+/// `vm.CoverURL` global or from a cover image template
+fn parse_cover_url(html: &str) -> Result<Option<String>> {
+    let cover_url = js::extract_raw(html, "vm.CoverURL").and_then(|expression| {
+        js::string_literals(expression).find(|literal| literal.starts_with("http"))
+    });
+    let cover_url = match cover_url {
+        Some(cover_url) => Some(cover_url),
+        None => Regex::new(r#"src="(https?://[^"{]+/cover/)\{\{"#)?
+            .captures(html)?
+            .and_then(|cap| cap.get(1).map(|m| m.as_str().to_string())),
+    };
+
+    Ok(cover_url.and_then(|cover_url| normalize_cover_url(&cover_url)))
+}
+
+fn normalize_cover_url(cover_url: &str) -> Option<String> {
+    let parsed = url::Url::parse(cover_url.trim()).ok()?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return None;
+    }
+
+    Some(parsed.as_str().trim_end_matches('/').to_string())
+}
+
 pub fn get_all_manga(url: &str, client: &Agent) -> Result<Vec<Dir>> {
     let html = client.get(&format!("{}/search", url))
         .call()?
         .into_string()?;
-    let start_index = html
-        .find("vm.Directory =")
-        .ok_or_else(|| anyhow!("vm.Directory not found"))?;
-    let dir = &html[start_index + 15..];
-    let end_index = dir
-        .find("}];")
-        .ok_or_else(|| anyhow!("vm.Directory not found"))?;
-    let vm_dir = dir[..end_index + 2].to_string();
-    Ok(serde_json::from_str::<Vec<Dir>>(&vm_dir)?)
+    parse_directory(&html)
+}
+
+/// How long the search page directory is reused
+const DIRECTORY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Series listed on the search page and the cover base url it declares
+#[derive(Debug, Clone)]
+struct Directory {
+    dirs: Vec<Dir>,
+    cover_url: Option<String>,
+}
+
+/// Search page directories by site url, kept for `ttl`
+struct DirectoryCache {
+    ttl: Duration,
+    directories: RwLock<HashMap<String, (Instant, Directory)>>,
+}
+
+impl DirectoryCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            directories: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Cached directory of `url`, calling `fetch` when it is missing or older
+    /// than `ttl`
+    fn get_or_fetch<F>(&self, url: &str, fetch: F) -> Result<Directory>
+    where
+        F: FnOnce() -> Result<Directory>,
+    {
+        if let Ok(directories) = self.directories.read() {
+            if let Some((fetched_at, directory)) = directories.get(url) {
+                if fetched_at.elapsed() < self.ttl {
+                    return Ok(directory.clone());
+                }
+            }
+        }
+
+        let directory = fetch()?;
+        if let Ok(mut directories) = self.directories.write() {
+            directories.insert(url.to_string(), (Instant::now(), directory.clone()));
+        }
+
+        Ok(directory)
+    }
+}
+
+lazy_static! {
+    static ref DIRECTORY_CACHE: DirectoryCache = DirectoryCache::new(DIRECTORY_TTL);
+}
+
+/// Get the directory along with the cover base url, `cover_url` takes
+/// precedence over the one found on the search page. The search page is
+/// several megabytes so it is fetched at most once per `DIRECTORY_TTL`
+fn get_directory(url: &str, cover_url: Option<&str>, client: &Agent) -> Result<(Vec<Dir>, String)> {
+    let directory = DIRECTORY_CACHE.get_or_fetch(url, || {
+        let html = client.get(&format!("{}/search", url))
+            .call()?
+            .into_string()?;
+        Ok(Directory {
+            dirs: parse_directory(&html)?,
+            cover_url: parse_cover_url(&html)?,
+        })
+    })?;
+    let cover_url = cover_url
+        .and_then(normalize_cover_url)
+        .or(directory.cover_url)
+        .unwrap_or_else(|| DEFAULT_COVER_URL.to_string());
+
+    Ok((directory.dirs, cover_url))
+}
+
+fn resolve_cover_url(cover_url: Option<&str>, html: &str) -> Result<String> {
+    Ok(match cover_url.and_then(normalize_cover_url) {
+        Some(cover_url) => cover_url,
+        None => parse_cover_url(html)?.unwrap_or_else(|| DEFAULT_COVER_URL.to_string()),
+    })
+}
+
+fn paginate(source_id: i64, dirs: &[Dir], mut page: i64, cover_url: &str) -> Vec<MangaInfo> {
+    if page < 1 {
+        page = 1;
+    }
+    let offset = (page - 1) * 20;
+
+    dirs.iter()
+        .skip(offset as usize)
+        .take(20)
+        .map(|dir| dir.to_manga_info(source_id, cover_url))
+        .collect()
 }
 
 fn sort_popular(dirs: &mut Vec<Dir>, asc: bool) {
@@ -243,16 +384,6 @@ fn sort_popular(dirs: &mut Vec<Dir>, asc: bool) {
     });
 }
 
-fn sort_latest(dirs: &mut Vec<Dir>, asc: bool) {
-    dirs.sort_by(|a, b| {
-        if asc {
-            a.lt.cmp(&b.lt)
-        } else {
-            b.lt.cmp(&a.lt)
-        }
-    });
-}
-
 fn sort_alphabetically(dirs: &mut Vec<Dir>, asc: bool) {
     dirs.sort_by(|a, b| if asc { a.s.cmp(&b.s) } else { b.s.cmp(&a.s) });
 }
@@ -269,46 +400,94 @@ fn sort_year_released(dirs: &mut Vec<Dir>, asc: bool) {
     });
 }
 
-pub fn get_popular_manga(source_id: i64, url: &str, mut page: i64, client: &Agent) -> Result<Vec<MangaInfo>> {
-    if page < 1 {
-        page = 1;
-    }
-    let offset = (page - 1) * 20;
-    let mut dirs = get_all_manga(url, client)?;
+pub fn get_popular_manga(
+    source_id: i64,
+    url: &str,
+    page: i64,
+    cover_url: Option<&str>,
+    client: &Agent,
+) -> Result<Vec<MangaInfo>> {
+    let (mut dirs, cover_url) = get_directory(url, cover_url, client)?;
     sort_popular(&mut dirs, false);
 
-    let manga = dirs
+    Ok(paginate(source_id, &dirs, page, &cover_url))
+}
+
+/// A series from the home page latest chapters feed
+#[derive(Debug)]
+pub struct LatestUpdate {
+    pub manga: MangaInfo,
+    /// Number of the newest chapter
+    pub chapter_number: f64,
+    /// Upload time of the newest chapter
+    pub uploaded: i64,
+}
+
+impl LatestUpdate {
+    /// The series with its newest chapter as description, the only place the
+    /// latest list can show it
+    pub fn into_manga_info(self) -> MangaInfo {
+        let uploaded = chrono::NaiveDateTime::from_timestamp(self.uploaded, 0);
+        MangaInfo {
+            description: Some(format!(
+                "Latest chapter: {} ({})",
+                self.chapter_number,
+                uploaded.format("%Y-%m-%d")
+            )),
+            ..self.manga
+        }
+    }
+}
+
+fn parse_latest_chapters(html: &str) -> Result<Vec<LatestChapter>> {
+    let mut chapters: Vec<LatestChapter> = js::extract_json(html, "vm.LatestJSON")?;
+
+    // most recent first, then keep only the newest chapter of each series
+    chapters.sort_by(|a, b| b.date.cmp(&a.date));
+    let mut seen = HashSet::new();
+    chapters.retain(|chapter| seen.insert(chapter.index_name.clone()));
+
+    Ok(chapters)
+}
+
+/// Get series ordered by their most recent chapter from the home page
+pub fn get_latest_updates(
+    source_id: i64,
+    url: &str,
+    cover_url: Option<&str>,
+    client: &Agent,
+) -> Result<Vec<LatestUpdate>> {
+    let html = client.get(url).call()?.into_string()?;
+    let cover_url = resolve_cover_url(cover_url, &html)?;
+
+    Ok(parse_latest_chapters(&html)?
         .iter()
-        .skip(offset as usize)
-        .take(20)
-        .map(|dir| {
-            let mut manga: MangaInfo = dir.into();
-            manga.source_id = source_id;
-            manga
+        .map(|chapter| LatestUpdate {
+            manga: chapter.to_manga_info(source_id, &cover_url),
+            chapter_number: chapter_number(&chapter.chapter),
+            uploaded: chapter.date.timestamp(),
         })
-        .collect();
-    Ok(manga)
+        .collect())
 }
 
-pub fn get_latest_manga(source_id: i64, url: &str, mut page: i64, client: &Agent) -> Result<Vec<MangaInfo>> {
+pub fn get_latest_manga(
+    source_id: i64,
+    url: &str,
+    mut page: i64,
+    cover_url: Option<&str>,
+    client: &Agent,
+) -> Result<Vec<MangaInfo>> {
     if page < 1 {
         page = 1;
     }
     let offset = (page - 1) * 20;
-    let mut dirs = get_all_manga(url, client)?;
-    sort_latest(&mut dirs, false);
 
-    let manga = dirs
-        .iter()
+    Ok(get_latest_updates(source_id, url, cover_url, client)?
+        .into_iter()
         .skip(offset as usize)
         .take(20)
-        .map(|dir| {
-            let mut manga: MangaInfo = dir.into();
-            manga.source_id = source_id;
-            manga
-        })
-        .collect();
-    Ok(manga)
+        .map(LatestUpdate::into_manga_info)
+        .collect())
 }
 
 fn filter_genre(dirs: &mut Vec<Dir>, genres: &[Input]) {
@@ -374,21 +553,17 @@ fn filter_scan_status(dirs: &mut Vec<Dir>, status: &str) {
 pub fn search_manga(
     source_id: i64,
     url: &str,
-    mut page: i64,
+    page: i64,
     query: Option<String>,
     filters: Option<Vec<Input>>,
+    cover_url: Option<&str>,
     client: &Agent,
 ) -> Result<Vec<MangaInfo>> {
     if query.is_none() && filters.is_none() {
         bail!("query and filters cannot be both empty")
     }
 
-    if page < 1 {
-        page = 1;
-    }
-
-    let offset = (page - 1) * 20;
-    let mut dirs = get_all_manga(url, client)?;
+    let (mut dirs, cover_url) = get_directory(url, cover_url, client)?;
 
     if let Some(filters) = filters {
         for filter in filters.iter() {
@@ -441,108 +616,83 @@ pub fn search_manga(
         filter_keyword(&mut dirs, &query);
     }
 
-    let manga = dirs
-        .iter()
-        .skip(offset as usize)
-        .take(20)
-        .map(|dir| {
-            let mut manga: MangaInfo = dir.into();
-            manga.source_id = source_id;
-            manga
-        })
-        .collect();
-    Ok(manga)
+    Ok(paginate(source_id, &dirs, page, &cover_url))
 }
 
-pub fn get_manga_detail(source_id: i64, url: &str, path: String, client: &Agent) -> Result<MangaInfo> {
-    let body = client.get(&format!("{}{}", url, path))
-        .call()?
-        .into_string()?;
-    let doc = Html::parse_document(&body);
-
-    let title = doc
-        .select(
-            &Selector::parse("li[class=\"list-group-item d-none d-sm-block\"] h1")
-                .map_err(|e| anyhow!("failed to parse selector: {:?}", e))?,
-        )
-        .next()
-        .and_then(|el| el.text().next().map(|e| e.to_string()))
-        .unwrap_or_default();
+fn get_description(body: &str) -> Result<Option<String>> {
+    let doc = Html::parse_document(body);
     let description = doc
         .select(
-            &Selector::parse("div[class=\"top-5 Content\"]")
-                .map_err(|e| anyhow!("failed to parse selector: {:?}", e))?,
-        )
-        .next()
-        .and_then(|el| el.text().next().map(|e| e.to_string()));
-    let author = doc
-        .select(
-            &Selector::parse("a[href^=\"/search/?author=\"]")
-                .map_err(|e| anyhow!("failed to parse selector: {:?}", e))?,
-        )
-        .next()
-        .map(|el| el.text().into_iter().map(|s| s.to_string()).collect())
-        .unwrap_or_else(Vec::new);
-    let genre = doc
-        .select(
-            &Selector::parse("a[href^=\"/search/?genre=\"]")
-                .map_err(|e| anyhow!("failed to parse selector: {:?}", e))?,
-        )
-        .next()
-        .map(|el| el.text().into_iter().map(|s| s.to_string()).collect())
-        .unwrap_or_else(Vec::new);
-    let status = doc
-        .select(
-            &Selector::parse("a[href^=\"/search/?status=\"]")
+            &Selector::parse("div.Content")
                 .map_err(|e| anyhow!("failed to parse selector: {:?}", e))?,
         )
         .next()
-        .and_then(|el| el.text().next().map(|s| s.replace("/search/?status=", "")));
-    let cover_url = doc
-        .select(
-            &Selector::parse("img[class=\"img-fluid bottom-5\"]")
-                .map_err(|e| anyhow!("failed to parse selector: {:?}", e))?,
-        )
-        .next()
-        .and_then(|el| el.value().attr("src").map(str::to_string))
-        .unwrap_or_default();
-
-    Ok(MangaInfo {
-        source_id,
-        title,
-        author,
-        genre,
-        status,
-        description,
-        path,
-        cover_url,
-    })
-}
+        .map(|el| el.text().collect::<String>().trim().to_string())
+        .filter(|description| !description.is_empty());
 
-fn get_index_name(body: &str) -> Result<String> {
-    Ok(Regex::new(r#"(?<=vm\.IndexName = ").*(?=";)"#)?
-        .find(body)?
-        .ok_or_else(|| anyhow!("regext not found anything"))?
-        .as_str()
-        .to_string())
+    Ok(description)
 }
 
-fn get_vm_dir(body: &str) -> Result<String> {
-    Ok(Regex::new(r#"(?<=vm\.Chapters = )\[.*\](?=;)"#)?
-        .find(body)?
-        .ok_or_else(|| anyhow!("regext not found anything"))?
-        .as_str()
-        .to_string())
+fn parse_manga_detail(source_id: i64, dir: &Dir, body: &str, cover_url: &str) -> Result<MangaInfo> {
+    let mut manga = dir.to_manga_info(source_id, cover_url);
+
+    manga.status = if dir.ps.is_empty() || dir.ps == dir.ss {
+        Some(dir.ss.clone())
+    } else if dir.ss.is_empty() {
+        Some(dir.ps.clone())
+    } else {
+        Some(format!("{} (Scan: {})", dir.ps, dir.ss))
+    };
+
+    let mut description = vec![];
+    if let Some(content) = get_description(body)? {
+        description.push(content);
+    }
+    if !dir.al.is_empty() {
+        description.push(format!("Alternative Titles: {}", dir.al.join(", ")));
+    }
+    if !dir.y.is_empty() && dir.y != "0" {
+        description.push(format!("Year: {}", dir.y));
+    }
+    manga.description = (!description.is_empty()).then(|| description.join("\n\n"));
+
+    Ok(manga)
 }
 
-fn get_ch_dirs(vm_dir: &str, index_name: &str) -> Result<Vec<DirChapter>> {
-    Ok(serde_json::from_str::<Vec<DirChapter>>(vm_dir)?
+pub fn get_manga_detail(
+    source_id: i64,
+    url: &str,
+    path: String,
+    cover_url: Option<&str>,
+    client: &Agent,
+) -> Result<MangaInfo> {
+    let index_name = path.trim_start_matches("/manga/").trim_end_matches('/');
+    let (dirs, cover_url) = get_directory(url, cover_url, client)?;
+    let dir = dirs
         .iter()
-        .map(|d| DirChapter {
-            index_name: index_name.to_string(),
-            ..d.clone()
-        })
-        .collect())
+        .find(|dir| dir.i == index_name)
+        .ok_or_else(|| anyhow!("{} not found in directory", index_name))?;
+
+    let body = client.get(&format!("{}{}", url, path))
+        .call()?
+        .into_string()?;
+
+    parse_manga_detail(source_id, dir, &body, &cover_url)
+}
+
+fn get_index_name(body: &str) -> Result<String> {
+    js::extract_string(body, "vm.IndexName")
+}
+
+/// Decode a chapter code such as `101945` (index, chapter, decimal) into 194.5
+fn chapter_number(chapter: &str) -> f64 {
+    if chapter.len() < 3 {
+        return 0.0;
+    }
+
+    let mut number = chapter[1..].to_string();
+    number.insert(number.len() - 1, '.');
+    number.parse::<f64>().unwrap_or_default()
 }
 
 pub fn get_chapters(source_id: i64, url: &str, path: String, client: &Agent) -> Result<Vec<ChapterInfo>> {
@@ -550,13 +700,11 @@ pub fn get_chapters(source_id: i64, url: &str, path: String, client: &Agent) ->
         .call()?
         .into_string()?;
     let index_name = get_index_name(&body)?;
-    let vm_dir = get_vm_dir(&body)?;
-    let ch_dirs = get_ch_dirs(&vm_dir, &index_name)?;
+    let ch_dirs: Vec<DirChapter> = js::extract_json(&body, "vm.Chapters")?;
 
     let mut chapters = vec![];
     for ch in ch_dirs.iter() {
-        let mut chapter = ch.chapter.clone();
-        let t = chapter.remove(0);
+        let t = ch.chapter.chars().next().unwrap_or('1');
 
         /*
         vm.ChapterURLEncode = function(e) {
@@ -574,8 +722,7 @@ pub fn get_chapters(source_id: i64, url: &str, path: String, client: &Agent) ->
             "".to_string()
         };
 
-        chapter.insert(chapter.len() - 1, '.');
-        let number = chapter.parse::<f64>().unwrap_or_default();
+        let number = chapter_number(&ch.chapter);
 
         chapters.push(ChapterInfo {
             source_id,
@@ -590,54 +737,377 @@ pub fn get_chapters(source_id: i64, url: &str, path: String, client: &Agent) ->
     Ok(chapters)
 }
 
-pub fn get_pages(url: &str, path: String, client: &Agent) -> Result<Vec<String>> {   
-    let body = client.get(&format!("{}{}", url, path))
-        .call()?
-        .into_string()?;
-    let index_name = get_index_name(&body)?;
-    let cur_chapter = {
-        let mat = Regex::new(r"(?<=vm\.CurChapter = ){.*}(?=;)")?
-            .find(&body)?
-            .ok_or_else(|| anyhow!("regext not found anything"))?
-            .as_str()
-            .to_string();
-        serde_json::from_str::<CurChapter>(&mat)?
-    };
-    let cur_path_name = Regex::new(r#"(?<=vm\.CurPathName = ").*(?=";)"#)?
-        .find(&body)?
-        .ok_or_else(|| anyhow!("regext not found anything"))?
-        .as_str()
+/// Extensions tried, in order, when the reader page has no image template
+const PAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "webp"];
+
+fn get_cur_path_name(body: &str) -> Result<String> {
+    let cur_path_name = js::extract_string(body, "vm.CurPathName")?;
+
+    // CurPathName is usually a bare host, but some series are served from a
+    // sub path or carry a scheme
+    let cur_path_name = cur_path_name
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/')
         .to_string();
-    let directory = {
-        if cur_chapter.directory.is_empty() {
+    if cur_path_name.is_empty() {
+        bail!("vm.CurPathName is empty");
+    }
+
+    Ok(cur_path_name)
+}
+
+fn get_cur_chapter(body: &str) -> Result<CurChapter> {
+    js::extract_json(body, "vm.CurChapter")
+}
+
+/// Get the `ng-src` template of the reader image, e.g.
+/// `https://{{vm.CurPathName}}/manga/Name/{{vm.CurChapter.Directory == '' ? '' : vm.CurChapter.Directory+'/'}}{{vm.ChapterImage(vm.CurChapter.Chapter)}}-{{vm.PageImage(Page)}}.png`
+fn get_page_template(body: &str) -> Result<Option<String>> {
+    let doc = Html::parse_document(body);
+    let selector =
+        Selector::parse("img[ng-src]").map_err(|e| anyhow!("failed to parse selector: {:?}", e))?;
+
+    Ok(doc
+        .select(&selector)
+        .filter_map(|el| el.value().attr("ng-src"))
+        .find(|src| src.contains("vm.PageImage") && src.contains("vm.CurPathName"))
+        .map(|src| src.trim().to_string()))
+}
+
+struct PageImage {
+    cur_path_name: String,
+    index_name: String,
+    directory: String,
+    chapter_image: String,
+}
+
+impl PageImage {
+    fn new(cur_path_name: String, index_name: String, cur_chapter: &CurChapter) -> Self {
+        let directory = if cur_chapter.directory.is_empty() {
             "".to_string()
         } else {
             format!("{}/", cur_chapter.directory)
+        };
+
+        // vm.ChapterImage
+        let chapter_image = {
+            let chapter = cur_chapter.chapter[1..cur_chapter.chapter.len() - 1].to_string();
+            let odd = cur_chapter.chapter[cur_chapter.chapter.len() - 1..].to_string();
+            if odd == "0" {
+                chapter
+            } else {
+                format!("{}.{}", chapter, odd)
+            }
+        };
+
+        Self {
+            cur_path_name,
+            index_name,
+            directory,
+            chapter_image,
         }
-    };
-    let chapter_image = {
-        let chapter = cur_chapter.chapter[1..cur_chapter.chapter.len() - 1].to_string();
-        let odd = cur_chapter.chapter[cur_chapter.chapter.len() - 1..].to_string();
-        if odd == "0" {
-            chapter
-        } else {
-            format!("{}.{}", chapter, odd)
+    }
+
+    /// vm.PageImage
+    fn page_image(page: i32) -> String {
+        let s = format!("000{}", page);
+        s[(s.len() - 3)..].to_string()
+    }
+
+    /// Expand every `{{expression}}` of the reader template for a page
+    fn expand(&self, template: &str, page: i32) -> Result<String> {
+        let mut url = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            url.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| anyhow!("unclosed expression in {}", template))?;
+            let expression = &rest[start + 2..start + end];
+            if expression.contains("CurPathName") {
+                url.push_str(&self.cur_path_name);
+            } else if expression.contains("IndexName") {
+                url.push_str(&self.index_name);
+            } else if expression.contains("Directory") {
+                url.push_str(&self.directory);
+            } else if expression.contains("ChapterImage") {
+                url.push_str(&self.chapter_image);
+            } else if expression.contains("PageImage") {
+                url.push_str(&Self::page_image(page));
+            } else {
+                bail!("unknown expression {} in {}", expression, template);
+            }
+            rest = &rest[start + end + 2..];
         }
-    };
+        url.push_str(rest);
+
+        Ok(url)
+    }
+
+    fn with_extension(&self, page: i32, extension: &str) -> String {
+        format!(
+            "https://{}/manga/{}/{}{}-{}.{}",
+            self.cur_path_name,
+            self.index_name,
+            self.directory,
+            self.chapter_image,
+            Self::page_image(page),
+            extension
+        )
+    }
+}
+
+fn validate_page_url(page_url: String) -> Result<String> {
+    let parsed = url::Url::parse(&page_url)?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        bail!("invalid page url {}", page_url);
+    }
+
+    Ok(page_url)
+}
+
+/// Build page urls from a reader page, `probe` is used to check whether an
+/// image exists when the page has no usable template
+fn parse_pages<F>(body: &str, probe: F) -> Result<Vec<String>>
+where
+    F: Fn(&str) -> bool,
+{
+    let index_name = get_index_name(body)?;
+    let cur_chapter = get_cur_chapter(body)?;
+    let cur_path_name = get_cur_path_name(body)?;
+    let page_image = PageImage::new(cur_path_name, index_name, &cur_chapter);
 
     let page = cur_chapter.page.parse::<i32>().unwrap_or(0);
-    let mut pages = Vec::new();
-    for i in 1..page + 1 {
-        let page_image = {
-            let s = format!("000{}", i);
-            s[(s.len() - 3)..].to_string()
+    if page < 1 {
+        return Ok(vec![]);
+    }
+
+    if let Some(template) = get_page_template(body)? {
+        if let Ok(pages) = (1..page + 1)
+            .map(|i| page_image.expand(&template, i))
+            .map(|url| url.and_then(validate_page_url))
+            .collect::<Result<Vec<_>>>()
+        {
+            return Ok(pages);
+        }
+    }
+
+    let extension = PAGE_EXTENSIONS
+        .iter()
+        .find(|extension| probe(&page_image.with_extension(1, extension)))
+        .unwrap_or(&PAGE_EXTENSIONS[0]);
+
+    (1..page + 1)
+        .map(|i| validate_page_url(page_image.with_extension(i, extension)))
+        .collect()
+}
+
+pub fn get_pages(url: &str, path: String, client: &Agent) -> Result<Vec<String>> {
+    let body = client.get(&format!("{}{}", url, path))
+        .call()?
+        .into_string()?;
+
+    parse_pages(&body, |page_url| client.head(page_url).call().is_ok())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_manga_detail() {
+        let dirs = parse_directory(include_str!("../fixtures/search.html")).unwrap();
+        assert_eq!(dirs.len(), 2);
+
+        let dir = dirs.iter().find(|dir| dir.i == "Onepunch-Man").unwrap();
+        let manga = parse_manga_detail(
+            4,
+            dir,
+            include_str!("../fixtures/manga_detail.html"),
+            "https://temp.compsci88.com/cover",
+        )
+        .unwrap();
+
+        assert_eq!(manga.source_id, 4);
+        assert_eq!(manga.title, "Onepunch-Man");
+        assert_eq!(manga.path, "/manga/Onepunch-Man");
+        assert_eq!(
+            manga.cover_url,
+            "https://temp.compsci88.com/cover/Onepunch-Man.jpg"
+        );
+        assert_eq!(manga.author, vec!["ONE", "MURATA Yuusuke"]);
+        assert_eq!(manga.genre, vec!["Action", "Comedy", "Sci-fi", "Seinen", "Supernatural"]);
+        assert_eq!(manga.status.as_deref(), Some("Ongoing (Scan: Hiatus)"));
+
+        let description = manga.description.unwrap();
+        assert!(description.starts_with("Saitama is a hero who only became a hero for fun."));
+        assert!(description.contains("Alternative Titles: One Punch-Man, ワンパンマン"));
+        assert!(description.ends_with("Year: 2012"));
+    }
+
+    #[test]
+    fn test_parse_cover_url() {
+        let html = include_str!("../fixtures/search.html");
+        assert_eq!(
+            parse_cover_url(html).unwrap().as_deref(),
+            Some("https://temp.compsci88.com/cover")
+        );
+
+        let html = r#"vm.CoverURL = function(IndexName) { return 'https://cdn.example.org/covers/' + IndexName + '.jpg'; };"#;
+        assert_eq!(
+            parse_cover_url(html).unwrap().as_deref(),
+            Some("https://cdn.example.org/covers")
+        );
+
+        let html = r#"<img class="img-fluid" ng-src="https://axiostrailbaby.lastation.us/cover/{{Series.i}}.jpg">"#;
+        assert_eq!(
+            parse_cover_url(html).unwrap().as_deref(),
+            Some("https://axiostrailbaby.lastation.us/cover")
+        );
+
+        assert_eq!(parse_cover_url("<html></html>").unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_cover_url() {
+        let mut preferences = get_preferences();
+        assert_eq!(get_cover_url(&preferences), None);
+
+        if let Some(Input::Text { state, .. }) = preferences.first_mut() {
+            *state = Some(" https://covers.example.org/ ".to_string());
+        }
+        assert_eq!(
+            get_cover_url(&preferences).as_deref(),
+            Some("https://covers.example.org/")
+        );
+
+        let dir = parse_directory(include_str!("../fixtures/search.html")).unwrap();
+        let manga = dir[1].to_manga_info(
+            4,
+            &normalize_cover_url(&get_cover_url(&preferences).unwrap()).unwrap(),
+        );
+        assert_eq!(manga.source_id, 4);
+        assert_eq!(
+            manga.cover_url,
+            "https://covers.example.org/Tomo-chan-wa-Onna-no-ko.jpg"
+        );
+    }
+
+    #[test]
+    fn test_directory_cache() {
+        let cache = DirectoryCache::new(Duration::from_secs(60));
+        let fetches = std::cell::Cell::new(0);
+        let fetch = || {
+            fetches.set(fetches.get() + 1);
+            Ok(Directory {
+                dirs: parse_directory(include_str!("../fixtures/search.html"))?,
+                cover_url: None,
+            })
         };
 
-        pages.push(format!(
-            "https://{}/manga/{}/{}{}-{}.png",
-            cur_path_name, index_name, directory, chapter_image, page_image
-        ));
+        let directory = cache.get_or_fetch("https://manga4life.com", fetch).unwrap();
+        assert_eq!(directory.dirs.len(), 2);
+        let directory = cache.get_or_fetch("https://manga4life.com", fetch).unwrap();
+        assert_eq!(directory.dirs.len(), 2);
+        assert_eq!(fetches.get(), 1);
+
+        // each site has its own directory
+        cache.get_or_fetch("https://weebcentral.com", fetch).unwrap();
+        assert_eq!(fetches.get(), 2);
+
+        let cache = DirectoryCache::new(Duration::ZERO);
+        cache.get_or_fetch("https://manga4life.com", fetch).unwrap();
+        assert!(cache
+            .get_or_fetch("https://manga4life.com", || Err(anyhow!("offline")))
+            .is_err());
     }
 
-    Ok(pages)
+    #[test]
+    fn test_chapter_number() {
+        assert_eq!(chapter_number("101940"), 194.0);
+        assert_eq!(chapter_number("107315"), 731.5);
+        assert_eq!(chapter_number("200010"), 1.0);
+        assert_eq!(chapter_number(""), 0.0);
+    }
+
+    #[test]
+    fn test_parse_latest_chapters() {
+        let chapters = parse_latest_chapters(include_str!("../fixtures/home.html")).unwrap();
+
+        let index_names: Vec<&str> = chapters.iter().map(|c| c.index_name.as_str()).collect();
+        assert_eq!(index_names, vec!["Kingdom", "Onepunch-Man", "Tomo-chan-wa-Onna-no-ko"]);
+        assert_eq!(chapter_number(&chapters[0].chapter), 780.0);
+        assert_eq!(chapter_number(&chapters[1].chapter), 194.5);
+
+        let manga = LatestUpdate {
+            manga: chapters[1].to_manga_info(4, DEFAULT_COVER_URL),
+            chapter_number: chapter_number(&chapters[1].chapter),
+            uploaded: chapters[1].date.timestamp(),
+        }
+        .into_manga_info();
+        assert_eq!(manga.path, "/manga/Onepunch-Man");
+        assert_eq!(
+            manga.description.as_deref(),
+            Some("Latest chapter: 194.5 (2024-04-18)")
+        );
+
+        let manga = chapters[0].to_manga_info(4, DEFAULT_COVER_URL);
+        assert_eq!(manga.title, "Kingdom");
+        assert_eq!(manga.path, "/manga/Kingdom");
+        assert_eq!(manga.genre, vec!["Action", "Historical", "Seinen"]);
+        assert_eq!(manga.status.as_deref(), Some("Ongoing"));
+    }
+
+    #[test]
+    fn test_parse_pages_png_template() {
+        let body = include_str!("../fixtures/reader_png.html");
+
+        let pages = parse_pages(body, |_| panic!("template should not probe")).unwrap();
+        assert_eq!(pages.len(), 3);
+        assert_eq!(
+            pages[0],
+            "https://official.lowee.us/manga/Tomo-chan-wa-Onna-no-ko/0100-001.png"
+        );
+        assert_eq!(
+            pages[2],
+            "https://official.lowee.us/manga/Tomo-chan-wa-Onna-no-ko/0100-003.png"
+        );
+    }
+
+    #[test]
+    fn test_parse_pages_webp_template_with_path() {
+        let body = include_str!("../fixtures/reader_webp.html");
+
+        let pages = parse_pages(body, |_| panic!("template should not probe")).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(
+            pages[1],
+            "https://scans.hot.animeflv.io/cdn/manga/Kingdom/S2/0731.5-002.webp"
+        );
+    }
+
+    #[test]
+    fn test_parse_pages_probe_without_template() {
+        let body = include_str!("../fixtures/reader_no_template.html");
+
+        let pages = parse_pages(body, |url| url.ends_with(".jpg")).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(
+            pages[0],
+            "https://temp.compsci88.com/manga/Onepunch-Man/0001-001.jpg"
+        );
+
+        let pages = parse_pages(body, |_| false).unwrap();
+        assert!(pages[0].ends_with("0001-001.png"));
+    }
+
+    #[test]
+    fn test_parse_pages_invalid_host() {
+        let body = include_str!("../fixtures/reader_no_template.html")
+            .replace("temp.compsci88.com", "");
+
+        assert!(parse_pages(&body, |_| false).is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use cookie_store::CookieStore;
+pub use cookie_store::CookieStore;
 use ureq::Cookie;
 use cookie::SameSite;
 use ureq::{json, serde_json, AgentBuilder};
@@ -90,7 +90,8 @@ fn convert_flaresolverr_cookies_to_ureq_cookies(mut store: CookieStore, cookies:
 
 
 
-pub fn build_flaresolverr_client(url: &str, flaresolverr_url: &str) -> Result<Agent, Box<dyn Error>> {
+/// Solve the challenge of `url` with FlareSolverr
+pub fn get_flaresolverr_solution(url: &str, flaresolverr_url: &str) -> Result<FlareSolverrSolution, Box<dyn Error>> {
     let payload = json!({
         "cmd": "request.get",
         "url": url,
@@ -107,12 +108,47 @@ pub fn build_flaresolverr_client(url: &str, flaresolverr_url: &str) -> Result<Ag
         return Err(format!("FlareSolverr error: {}", body.message).into());
     }
 
-    let user_agent = body.solution.userAgent.clone();
-    let store = convert_flaresolverr_cookies_to_ureq_cookies(CookieStore::default(), body.solution.cookies);
+    Ok(body.solution)
+}
+
+/// Agent with the user agent and clearance cookies of a FlareSolverr solution
+/// added to `store`
+pub fn build_flaresolverr_agent(solution: &FlareSolverrSolution, store: CookieStore) -> Agent {
+    let store = convert_flaresolverr_cookies_to_ureq_cookies(store, solution.cookies.clone());
+
+    build_ureq_agent(Some(&solution.userAgent), Some(store))
+}
+
+pub fn build_flaresolverr_client(url: &str, flaresolverr_url: &str) -> Result<Agent, Box<dyn Error>> {
+    let solution = get_flaresolverr_solution(url, flaresolverr_url)?;
+
+    Ok(build_flaresolverr_agent(&solution, CookieStore::default()))
+}
+
+/// Add a `name=value` cookie for the host of `url`, e.g. a session copied from a browser
+pub fn insert_cookie(store: &mut CookieStore, url: &str, name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let request_url = Url::parse(url)?;
+    let domain = request_url.host_str().ok_or("url has no host")?.to_string();
+    let cookie = Cookie::build(name.to_string(), value.to_string())
+        .domain(domain)
+        .path("/")
+        .finish();
+
+    store.insert_raw(&cookie, &request_url)?;
+
+    Ok(())
+}
 
-    let agent = build_ureq_agent(Some(&user_agent), Some(store));
+/// Value of the cookie `name` the agent sends to `url`
+pub fn cookie_value(agent: &Agent, url: &str, name: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let store = agent.cookie_store();
+    let value = store
+        .get_request_values(&url)
+        .find(|(cookie, _)| *cookie == name)
+        .map(|(_, value)| value.to_string());
 
-    Ok(agent)
+    value
 }
 
 #[cfg(test)]
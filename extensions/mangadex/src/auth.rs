@@ -0,0 +1,292 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+pub static AUTH_URL: &str =
+    "https://auth.mangadex.org/realms/mangadex/protocol/openid-connect/token";
+
+/// Tokens are refreshed this long before they expire
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// How long a failed password login is remembered before trying again, so
+/// wrong credentials do not keep hitting `AUTH_URL`
+pub const LOGIN_RETRY: Duration = Duration::from_secs(5 * 60);
+
+/// Personal API client and the account it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Seconds
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Instant,
+}
+
+impl From<TokenResponse> for Session {
+    fn from(res: TokenResponse) -> Self {
+        Self {
+            access_token: res.access_token,
+            refresh_token: res.refresh_token,
+            expires_at: Instant::now() + Duration::from_secs(res.expires_in),
+        }
+    }
+}
+
+impl Session {
+    fn is_expired(&self) -> bool {
+        Instant::now() + EXPIRY_MARGIN >= self.expires_at
+    }
+}
+
+/// OAuth2 session of a MangaDex account
+#[derive(Debug)]
+pub struct Auth {
+    credentials: Option<Credentials>,
+    retry: Duration,
+    session: Mutex<Option<Session>>,
+    /// When the last password login failed
+    failed_at: Mutex<Option<Instant>>,
+}
+
+impl Default for Auth {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Auth {
+    pub fn new(credentials: Option<Credentials>) -> Self {
+        Self::with_retry(credentials, LOGIN_RETRY)
+    }
+
+    /// A session that waits `retry` after a failed password login
+    pub fn with_retry(credentials: Option<Credentials>, retry: Duration) -> Self {
+        Self {
+            credentials,
+            retry,
+            session: Mutex::new(None),
+            failed_at: Mutex::new(None),
+        }
+    }
+
+    pub fn credentials(&self) -> Option<&Credentials> {
+        self.credentials.as_ref()
+    }
+
+    /// A valid access token, `None` when there are no credentials. `request`
+    /// posts a token request form to `AUTH_URL`, an expired session is
+    /// refreshed and a password login is done when refreshing fails. A failed
+    /// password login is not tried again for `retry`
+    pub fn access_token<F>(&self, request: F) -> Result<Option<String>>
+    where
+        F: Fn(&[(&str, &str)]) -> Result<TokenResponse>,
+    {
+        let credentials = match &self.credentials {
+            Some(credentials) => credentials,
+            None => return Ok(None),
+        };
+
+        let mut session = self
+            .session
+            .lock()
+            .map_err(|_| anyhow!("session lock poisoned"))?;
+
+        if let Some(current) = session.as_ref() {
+            if !current.is_expired() {
+                return Ok(Some(current.access_token.clone()));
+            }
+        }
+
+        let refresh_token = session
+            .as_ref()
+            .and_then(|current| current.refresh_token.clone());
+        let refreshed = refresh_token.as_deref().and_then(|refresh_token| {
+            request(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("client_id", &credentials.client_id),
+                ("client_secret", &credentials.client_secret),
+            ])
+            .ok()
+        });
+
+        let next: Session = match refreshed {
+            // the refresh token stays valid when a refresh does not rotate it
+            Some(res) => Session {
+                refresh_token: res.refresh_token.clone().or(refresh_token),
+                ..res.into()
+            },
+            None => {
+                let mut failed_at = self
+                    .failed_at
+                    .lock()
+                    .map_err(|_| anyhow!("login lock poisoned"))?;
+                if let Some(failed_at) = *failed_at {
+                    if failed_at.elapsed() < self.retry {
+                        bail!("MangaDex login failed recently, not retrying yet");
+                    }
+                }
+
+                match request(&[
+                    ("grant_type", "password"),
+                    ("username", &credentials.username),
+                    ("password", &credentials.password),
+                    ("client_id", &credentials.client_id),
+                    ("client_secret", &credentials.client_secret),
+                ]) {
+                    Ok(res) => {
+                        *failed_at = None;
+                        res.into()
+                    }
+                    Err(e) => {
+                        *failed_at = Some(Instant::now());
+                        return Err(e);
+                    }
+                }
+            }
+        };
+
+        let access_token = next.access_token.clone();
+        *session = Some(next);
+
+        Ok(Some(access_token))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            username: "reader".to_string(),
+            password: "hunter2".to_string(),
+            client_id: "personal-client-reader".to_string(),
+            client_secret: "secret".to_string(),
+        }
+    }
+
+    fn token(access_token: &str, expires_in: u64) -> TokenResponse {
+        serde_json::from_str(&format!(
+            r#"{{"access_token": "{access_token}", "expires_in": {expires_in}, "refresh_expires_in": 7776000, "refresh_token": "refresh-{access_token}", "token_type": "Bearer"}}"#
+        ))
+        .unwrap()
+    }
+
+    fn grant(form: &[(&str, &str)]) -> String {
+        form.iter()
+            .find(|(key, _)| *key == "grant_type")
+            .map(|(_, value)| value.to_string())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_anonymous() {
+        let auth = Auth::new(None);
+        let token = auth.access_token(|_| panic!("anonymous sessions do not log in"));
+        assert_eq!(token.unwrap(), None);
+    }
+
+    #[test]
+    fn test_login_and_reuse() {
+        let auth = Auth::new(Some(credentials()));
+        let grants = RefCell::new(vec![]);
+        let request = |form: &[(&str, &str)]| {
+            grants.borrow_mut().push(grant(form));
+            assert!(form.contains(&("username", "reader")));
+            assert!(form.contains(&("client_secret", "secret")));
+            Ok(token("first", 900))
+        };
+
+        assert_eq!(auth.access_token(request).unwrap().unwrap(), "first");
+        assert_eq!(auth.access_token(request).unwrap().unwrap(), "first");
+        assert_eq!(*grants.borrow(), vec!["password"]);
+    }
+
+    #[test]
+    fn test_refresh_expired_session() {
+        let auth = Auth::new(Some(credentials()));
+        auth.access_token(|_| Ok(token("first", 0))).unwrap();
+
+        let token = auth.access_token(|form| {
+            assert_eq!(grant(form), "refresh_token");
+            assert!(form.contains(&("refresh_token", "refresh-first")));
+            Ok(token("second", 900))
+        });
+        assert_eq!(token.unwrap().unwrap(), "second");
+    }
+
+    #[test]
+    fn test_refresh_keeps_refresh_token() {
+        let auth = Auth::new(Some(credentials()));
+        auth.access_token(|_| Ok(token("first", 0))).unwrap();
+
+        auth.access_token(|_| {
+            Ok(serde_json::from_str(r#"{"access_token": "second", "expires_in": 0}"#).unwrap())
+        })
+        .unwrap();
+        let token = auth.access_token(|form| {
+            assert!(form.contains(&("refresh_token", "refresh-first")));
+            Ok(token("third", 900))
+        });
+        assert_eq!(token.unwrap().unwrap(), "third");
+    }
+
+    #[test]
+    fn test_failed_login_not_retried() {
+        let auth = Auth::new(Some(credentials()));
+        let attempts = RefCell::new(0);
+        let request = |_: &[(&str, &str)]| -> Result<TokenResponse> {
+            *attempts.borrow_mut() += 1;
+            bail!("invalid_grant")
+        };
+
+        assert!(auth.access_token(request).is_err());
+        assert!(auth.access_token(request).is_err());
+        assert_eq!(*attempts.borrow(), 1);
+
+        let auth = Auth::with_retry(Some(credentials()), Duration::ZERO);
+        assert!(auth.access_token(|_| bail!("invalid_grant")).is_err());
+        assert_eq!(
+            auth.access_token(|_| Ok(token("first", 900)))
+                .unwrap()
+                .unwrap(),
+            "first"
+        );
+    }
+
+    #[test]
+    fn test_login_when_refresh_fails() {
+        let auth = Auth::new(Some(credentials()));
+        auth.access_token(|_| Ok(token("first", 0))).unwrap();
+
+        let grants = RefCell::new(vec![]);
+        let token = auth.access_token(|form| {
+            grants.borrow_mut().push(grant(form));
+            match grant(form).as_str() {
+                "refresh_token" => bail!("invalid_grant"),
+                _ => Ok(token("second", 900)),
+            }
+        });
+        assert_eq!(token.unwrap().unwrap(), "second");
+        assert_eq!(*grants.borrow(), vec!["refresh_token", "password"]);
+    }
+}
@@ -0,0 +1,365 @@
+//! Convert the BBCode of MangaDex descriptions to Markdown
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    /// `[*]` of a list
+    Item(Vec<Node>),
+    Tag {
+        name: String,
+        arg: Option<String>,
+        children: Vec<Node>,
+    },
+}
+
+/// Tags rendered with their own markup, other known tags are dropped and their
+/// content kept, unknown tags stay as text
+const KNOWN_TAGS: &[&str] = &[
+    "b", "i", "u", "s", "url", "spoiler", "list", "ul", "ol", "hr", "quote", "code", "img",
+    "center", "left", "right", "sup", "sub", "h1", "h2", "h3",
+];
+
+fn is_known(name: &str) -> bool {
+    KNOWN_TAGS.contains(&name)
+}
+
+fn is_void(name: &str) -> bool {
+    name == "hr"
+}
+
+enum Token {
+    Text(String),
+    Open { name: String, arg: Option<String> },
+    Close(String),
+    Item,
+}
+
+/// The tag starting at `[`, with the length of its source
+fn parse_tag(source: &str) -> Option<(Token, usize)> {
+    let end = source.find(']')?;
+    let inner = &source[1..end];
+    if inner.contains('[') || inner.contains('\n') {
+        return None;
+    }
+
+    let token = if inner == "*" {
+        Token::Item
+    } else if let Some(name) = inner.strip_prefix('/') {
+        let name = name.trim().to_lowercase();
+        if !is_known(&name) {
+            return None;
+        }
+        Token::Close(name)
+    } else {
+        let (name, arg) = match inner.split_once('=') {
+            Some((name, arg)) => (name, Some(arg.trim().trim_matches('"').to_string())),
+            None => (inner, None),
+        };
+        let name = name.trim().to_lowercase();
+        if !is_known(&name) {
+            return None;
+        }
+        Token::Open { name, arg }
+    };
+
+    Some((token, end + 1))
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut text = String::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('[') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match parse_tag(rest) {
+            Some((token, len)) => {
+                if !text.is_empty() {
+                    tokens.push(Token::Text(std::mem::take(&mut text)));
+                }
+                tokens.push(token);
+                rest = &rest[len..];
+            }
+            None => {
+                text.push('[');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+
+    tokens
+}
+
+struct Frame {
+    name: String,
+    arg: Option<String>,
+    children: Vec<Node>,
+    /// Content of the current `[*]`, only used by lists
+    item: Option<Vec<Node>>,
+}
+
+impl Frame {
+    fn push(&mut self, node: Node) {
+        match self.item.as_mut() {
+            Some(item) => item.push(node),
+            None => self.children.push(node),
+        }
+    }
+
+    fn finish_item(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.children.push(Node::Item(item));
+        }
+    }
+
+    fn into_node(mut self) -> Node {
+        self.finish_item();
+        Node::Tag {
+            name: self.name,
+            arg: self.arg,
+            children: self.children,
+        }
+    }
+}
+
+fn is_list(name: &str) -> bool {
+    matches!(name, "list" | "ul" | "ol")
+}
+
+/// Parse into a tree, unclosed tags are closed at the end of their parent and
+/// stray closing tags are kept as text
+fn parse(source: &str) -> Vec<Node> {
+    let mut stack = vec![Frame {
+        name: String::new(),
+        arg: None,
+        children: vec![],
+        item: None,
+    }];
+
+    for token in tokenize(source) {
+        match token {
+            Token::Text(text) => stack.last_mut().unwrap().push(Node::Text(text)),
+            Token::Open { name, arg } if is_void(&name) => {
+                stack.last_mut().unwrap().push(Node::Tag {
+                    name,
+                    arg,
+                    children: vec![],
+                })
+            }
+            Token::Open { name, arg } => stack.push(Frame {
+                name,
+                arg,
+                children: vec![],
+                item: None,
+            }),
+            Token::Item => {
+                // close inline tags left open in the previous item
+                while stack.len() > 1 && !is_list(&stack.last().unwrap().name) {
+                    let node = stack.pop().unwrap().into_node();
+                    stack.last_mut().unwrap().push(node);
+                }
+                let frame = stack.last_mut().unwrap();
+                if is_list(&frame.name) {
+                    frame.finish_item();
+                    frame.item = Some(vec![]);
+                } else {
+                    frame.push(Node::Text("[*]".to_string()));
+                }
+            }
+            Token::Close(name) => {
+                let open = stack[1..]
+                    .iter()
+                    .rposition(|frame| frame.name == name)
+                    .map(|i| i + 1);
+                match open {
+                    Some(open) => {
+                        while stack.len() > open {
+                            let node = stack.pop().unwrap().into_node();
+                            stack.last_mut().unwrap().push(node);
+                        }
+                    }
+                    None => stack
+                        .last_mut()
+                        .unwrap()
+                        .push(Node::Text(format!("[/{}]", name))),
+                }
+            }
+        }
+    }
+
+    while stack.len() > 1 {
+        let node = stack.pop().unwrap().into_node();
+        stack.last_mut().unwrap().push(node);
+    }
+
+    stack.pop().unwrap().children
+}
+
+fn render_all(nodes: &[Node], depth: usize) -> String {
+    nodes.iter().map(|node| render(node, depth)).collect()
+}
+
+/// Wrap `content` in `marker`, whitespace stays outside so the emphasis is valid
+fn emphasis(content: &str, marker: &str) -> String {
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        return content.to_string();
+    }
+
+    let start = content.len() - content.trim_start().len();
+    let end = content.trim_end().len();
+    format!(
+        "{}{}{}{}{}",
+        &content[..start],
+        marker,
+        trimmed,
+        marker,
+        &content[end..]
+    )
+}
+
+/// `content` as a block quote, Markdown has no spoiler syntax so spoilers are
+/// quoted too
+fn blockquote(content: &str) -> String {
+    let quoted: Vec<String> = content
+        .trim()
+        .lines()
+        .map(|line| format!("> {}", line).trim_end().to_string())
+        .collect();
+    format!("\n\n{}\n\n", quoted.join("\n"))
+}
+
+fn render(node: &Node, depth: usize) -> String {
+    let (name, arg, children) = match node {
+        Node::Text(text) => return text.clone(),
+        Node::Item(children) => return render_all(children, depth),
+        Node::Tag {
+            name,
+            arg,
+            children,
+        } => (name.as_str(), arg.as_deref(), children),
+    };
+
+    match name {
+        "b" => emphasis(&render_all(children, depth), "**"),
+        "i" => emphasis(&render_all(children, depth), "*"),
+        "s" => emphasis(&render_all(children, depth), "~~"),
+        "spoiler" => {
+            let content = render_all(children, depth);
+            blockquote(&format!("Spoiler: {}", content.trim()))
+        }
+        "code" => emphasis(&render_all(children, depth), "`"),
+        "url" => {
+            let text = render_all(children, depth);
+            let href = arg.unwrap_or_else(|| text.trim());
+            if href.is_empty() {
+                text
+            } else if text.trim().is_empty() || text.trim() == href {
+                format!("<{}>", href)
+            } else {
+                format!("[{}]({})", text.trim(), href)
+            }
+        }
+        "img" => {
+            let src = render_all(children, depth);
+            format!("![]({})", src.trim())
+        }
+        "hr" => "\n\n---\n\n".to_string(),
+        "h1" | "h2" | "h3" => {
+            let level = name[1..].parse().unwrap_or(1);
+            format!(
+                "\n\n{} {}\n\n",
+                "#".repeat(level),
+                render_all(children, depth).trim()
+            )
+        }
+        "quote" => blockquote(&render_all(children, depth)),
+        "list" | "ul" | "ol" => {
+            let ordered = name == "ol" || matches!(arg, Some(arg) if arg == "1");
+            let items: Vec<String> = children
+                .iter()
+                .filter_map(|child| match child {
+                    Node::Item(item) => Some(render_all(item, depth + 1)),
+                    _ => None,
+                })
+                .enumerate()
+                .map(|(i, item)| {
+                    let bullet = if ordered {
+                        format!("{}.", i + 1)
+                    } else {
+                        "-".to_string()
+                    };
+                    // continuation lines line up with the content of the item
+                    let indent = " ".repeat(bullet.len() + 1);
+                    let item = item.trim().replace('\n', &format!("\n{}", indent));
+                    format!("{} {}", bullet, item)
+                })
+                .collect();
+            if depth == 0 {
+                format!("\n\n{}\n\n", items.join("\n"))
+            } else {
+                format!("\n{}", items.join("\n"))
+            }
+        }
+        _ => render_all(children, depth),
+    }
+}
+
+/// Markdown of a BBCode description
+pub fn to_markdown(bbcode: &str) -> String {
+    let source = bbcode.replace("\r\n", "\n").replace('\r', "\n");
+    let markdown = render_all(&parse(&source), 0);
+
+    // collapse the blank lines left around block elements
+    let mut result = String::new();
+    let mut newlines = 0;
+    for c in markdown.trim().chars() {
+        if c == '\n' {
+            newlines += 1;
+            if newlines > 2 {
+                continue;
+            }
+        } else {
+            newlines = 0;
+        }
+        result.push(c);
+    }
+
+    result
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Case {
+        name: String,
+        bbcode: String,
+        markdown: String,
+    }
+
+    #[test]
+    fn test_descriptions() {
+        let cases: Vec<Case> =
+            serde_json::from_str(include_str!("../fixtures/descriptions.json")).unwrap();
+        for case in cases {
+            assert_eq!(
+                to_markdown(&case.bbcode),
+                case.markdown,
+                "case {}",
+                case.name
+            );
+        }
+    }
+}
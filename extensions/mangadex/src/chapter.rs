@@ -0,0 +1,317 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::dto::manga::Aggregate;
+use crate::dto::Relationship;
+
+/// Step between synthetic numbers of unnumbered chapters
+const UNNUMBERED_STEP: f64 = 0.01;
+
+/// Chapter hosted on another site, e.g. MangaPlus, which has no pages on MangaDex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalChapter {
+    pub url: String,
+}
+
+impl fmt::Display for ExternalChapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chapter is only available at {}", self.url)
+    }
+}
+
+impl std::error::Error for ExternalChapter {}
+
+pub fn external_url(chapter: &Relationship) -> Option<&str> {
+    if let Relationship::Chapter {
+        attributes: Some(attr),
+        ..
+    } = chapter
+    {
+        attr.external_url.as_deref().filter(|url| !url.is_empty())
+    } else {
+        None
+    }
+}
+
+/// Scanlation group selection applied to the chapter feed
+#[derive(Debug, Clone, Default)]
+pub struct GroupFilter {
+    /// Group names or ids, earlier entries win
+    pub preferred: Vec<String>,
+    /// Group names or ids whose chapters are dropped
+    pub blocked: Vec<String>,
+    /// Keep a single upload for each chapter number
+    pub one_per_number: bool,
+}
+
+fn groups(chapter: &Relationship) -> Vec<(&str, &str)> {
+    if let Relationship::Chapter { relationships, .. } = chapter {
+        relationships
+            .iter()
+            .filter_map(|relationship| match relationship {
+                Relationship::ScanlationGroup { id, attributes } => Some((
+                    id.as_str(),
+                    attributes
+                        .as_ref()
+                        .map(|attr| attr.name.as_str())
+                        .unwrap_or_default(),
+                )),
+                _ => None,
+            })
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+/// Index of the first entry of `list` matching one of the chapter groups
+fn position(list: &[String], chapter: &Relationship) -> Option<usize> {
+    let groups = groups(chapter);
+    list.iter().position(|entry| {
+        groups
+            .iter()
+            .any(|(id, name)| entry == id || entry.eq_ignore_ascii_case(name))
+    })
+}
+
+fn chapter_number(chapter: &Relationship) -> Option<String> {
+    if let Relationship::Chapter {
+        attributes: Some(attr),
+        ..
+    } = chapter
+    {
+        attr.chapter
+            .as_ref()
+            .map(|number| number.trim().to_string())
+            .filter(|number| !number.is_empty())
+    } else {
+        None
+    }
+}
+
+fn publish_at(chapter: &Relationship) -> i64 {
+    if let Relationship::Chapter {
+        attributes: Some(attr),
+        ..
+    } = chapter
+    {
+        attr.publish_at.timestamp()
+    } else {
+        0
+    }
+}
+
+impl GroupFilter {
+    /// Whether `candidate` should replace `current` for the same number
+    fn is_better(&self, candidate: &Relationship, current: &Relationship) -> bool {
+        match (
+            position(&self.preferred, candidate),
+            position(&self.preferred, current),
+        ) {
+            (Some(a), Some(b)) if a != b => a < b,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            _ => publish_at(candidate) > publish_at(current),
+        }
+    }
+
+    /// Drop chapters of blocked groups and, if enabled, keep only the
+    /// preferred or most recent upload of each chapter number. Unnumbered
+    /// chapters are always kept, the feed order is preserved.
+    pub fn apply(&self, chapters: Vec<Relationship>) -> Vec<Relationship> {
+        let chapters: Vec<Relationship> = chapters
+            .into_iter()
+            .filter(|chapter| position(&self.blocked, chapter).is_none())
+            .collect();
+        if !self.one_per_number {
+            return chapters;
+        }
+
+        let mut selected: Vec<Option<Relationship>> = vec![];
+        let mut by_number: HashMap<String, usize> = HashMap::new();
+        for chapter in chapters {
+            let number = match chapter_number(&chapter) {
+                Some(number) => number,
+                None => {
+                    selected.push(Some(chapter));
+                    continue;
+                }
+            };
+
+            if let Some(index) = by_number.get(&number) {
+                let current = selected[*index].as_ref();
+                if current.map_or(true, |current| self.is_better(&chapter, current)) {
+                    selected[*index] = Some(chapter);
+                }
+            } else {
+                by_number.insert(number, selected.len());
+                selected.push(Some(chapter));
+            }
+        }
+
+        selected.into_iter().flatten().collect()
+    }
+}
+
+/// Aggregate keys in reading order, numbers ascending and "none" last
+fn by_number(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// Chapter number of every chapter id of the aggregate. Unnumbered chapters,
+/// e.g. extras and oneshots, follow the chapter before them in reading order,
+/// so an extra after chapter 12 becomes 12.01
+pub fn chapter_numbers(aggregate: &Aggregate) -> HashMap<String, f64> {
+    let mut volumes: Vec<_> = aggregate.volumes.iter().collect();
+    volumes.sort_by(|(a, _), (b, _)| by_number(a, b));
+
+    let mut numbers = HashMap::new();
+    let mut last = 0.0;
+    for (_, volume) in volumes {
+        let mut chapters: Vec<_> = volume.chapters.iter().collect();
+        chapters.sort_by(|(a, _), (b, _)| by_number(a, b));
+
+        for (key, chapter) in chapters {
+            let ids = std::iter::once(&chapter.id).chain(chapter.others.iter());
+            match key.parse::<f64>() {
+                Ok(number) => {
+                    last = number;
+                    numbers.extend(ids.map(|id| (id.clone(), number)));
+                }
+                Err(_) => {
+                    for (i, id) in ids.enumerate() {
+                        numbers.insert(id.clone(), last + (i + 1) as f64 * UNNUMBERED_STEP);
+                    }
+                }
+            }
+        }
+    }
+
+    numbers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dto::{Data, Results};
+
+    fn feed() -> Vec<Relationship> {
+        let res: Results =
+            serde_json::from_str(include_str!("../fixtures/feed_groups.json")).unwrap();
+        match res.data {
+            Data::Multiple { data, .. } => data,
+            _ => panic!("expected a collection"),
+        }
+    }
+
+    fn ids(chapters: &[Relationship]) -> Vec<&str> {
+        chapters
+            .iter()
+            .map(|chapter| &chapter.id()[chapter.id().len() - 1..])
+            .collect()
+    }
+
+    #[test]
+    fn test_default_keeps_everything() {
+        let chapters = GroupFilter::default().apply(feed());
+        assert_eq!(ids(&chapters), vec!["1", "2", "3", "4", "5", "6", "7"]);
+    }
+
+    #[test]
+    fn test_blocked_groups() {
+        let filter = GroupFilter {
+            blocked: vec!["bad scans".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&filter.apply(feed())),
+            vec!["1", "2", "3", "5", "6", "7"]
+        );
+
+        let filter = GroupFilter {
+            blocked: vec!["6f1a7e2c-0000-4000-8000-000000000002".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(ids(&filter.apply(feed())), vec!["1", "4", "6"]);
+    }
+
+    #[test]
+    fn test_one_per_number_most_recent() {
+        let filter = GroupFilter {
+            one_per_number: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(&filter.apply(feed())), vec!["2", "3", "4", "6", "7"]);
+    }
+
+    #[test]
+    fn test_one_per_number_preferred() {
+        let filter = GroupFilter {
+            preferred: vec!["Group One".to_string(), "Group Two".to_string()],
+            blocked: vec!["Bad Scans".to_string()],
+            one_per_number: true,
+        };
+        assert_eq!(ids(&filter.apply(feed())), vec!["1", "3", "5", "6", "7"]);
+    }
+
+    #[test]
+    fn test_external_url() {
+        let res: Results =
+            serde_json::from_str(include_str!("../fixtures/chapter_external.json")).unwrap();
+        let chapter = match res.data {
+            Data::Single { data } => data,
+            _ => panic!("expected an entity"),
+        };
+        assert_eq!(
+            external_url(&chapter),
+            Some("https://mangaplus.shueisha.co.jp/viewer/1000486")
+        );
+        assert!(feed().iter().all(|chapter| external_url(chapter).is_none()));
+
+        let error = anyhow::Error::from(ExternalChapter {
+            url: external_url(&chapter).unwrap().to_string(),
+        });
+        assert_eq!(
+            error.to_string(),
+            "chapter is only available at https://mangaplus.shueisha.co.jp/viewer/1000486"
+        );
+        assert!(error.downcast_ref::<ExternalChapter>().is_some());
+    }
+
+    #[test]
+    fn test_chapter_numbers() {
+        let aggregate: Aggregate =
+            serde_json::from_str(include_str!("../fixtures/aggregate.json")).unwrap();
+        let numbers = chapter_numbers(&aggregate);
+
+        let mut numbers: Vec<(String, String)> = numbers
+            .into_iter()
+            .map(|(id, number)| (id[id.len() - 2..].to_string(), format!("{:.2}", number)))
+            .collect();
+        numbers.sort_by(|a, b| by_number(&a.1, &b.1).then(a.0.cmp(&b.0)));
+        assert_eq!(
+            numbers,
+            [
+                ("01", "1.00"),
+                ("11", "1.00"),
+                ("02", "2.00"),
+                ("03", "2.01"),
+                ("04", "3.00"),
+                ("05", "3.50"),
+                ("06", "4.00"),
+                ("07", "5.00"),
+                ("08", "5.01"),
+                ("09", "5.02"),
+            ]
+            .iter()
+            .map(|(id, number)| (id.to_string(), number.to_string()))
+            .collect::<Vec<_>>()
+        );
+    }
+}
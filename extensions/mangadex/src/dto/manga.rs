@@ -1,7 +1,7 @@
 use std::{collections::HashMap, fmt::Display, str::FromStr};
 
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::marker::PhantomData;
 
@@ -71,7 +71,34 @@ pub enum Demographic {
     None,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl Display for Demographic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Demographic::Shounen => write!(f, "shounen"),
+            Demographic::Shoujo => write!(f, "shoujo"),
+            Demographic::Josei => write!(f, "josei"),
+            Demographic::Seinen => write!(f, "seinen"),
+            Demographic::None => write!(f, "none"),
+        }
+    }
+}
+
+impl FromStr for Demographic {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shounen" => Ok(Demographic::Shounen),
+            "shoujo" => Ok(Demographic::Shoujo),
+            "josei" => Ok(Demographic::Josei),
+            "seinen" => Ok(Demographic::Seinen),
+            "none" => Ok(Demographic::None),
+            _ => Err(anyhow::anyhow!("no such demographic")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Rating {
     Safe,
@@ -115,26 +142,47 @@ pub enum Order {
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListOrder {
+    pub relevance: Option<Order>,
+    pub rating: Option<Order>,
+    pub followed_count: Option<Order>,
+    pub latest_uploaded_chapter: Option<Order>,
     pub created_at: Option<Order>,
     pub updated_at: Option<Order>,
-    pub followed_count: Option<Order>,
+    pub title: Option<Order>,
+    pub year: Option<Order>,
+    pub readable_at: Option<Order>,
+}
+
+/// MangaDex only accepts `YYYY-MM-DDTHH:MM:SS` for `*Since` parameters
+fn serialize_since<S>(since: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match since {
+        Some(since) => serializer.serialize_str(&since.format("%Y-%m-%dT%H:%M:%S").to_string()),
+        None => serializer.serialize_none(),
+    }
 }
 
 pub type Map = HashMap<String, String>;
 
-fn sequence_or_map<'de, D>(deserializer: D) -> Result<Map, D::Error>
+fn sequence_or_map<'de, D, V>(deserializer: D) -> Result<HashMap<String, V>, D::Error>
 where
     D: Deserializer<'de>,
+    V: Deserialize<'de>,
 {
     // This is a Visitor that forwards string types to T's `FromStr` impl and
     // forwards map types to T's `Deserialize` impl. The `PhantomData` is to
     // keep the compiler from complaining about T being an unused generic type
     // parameter. We need T in order to know the Value type for the Visitor
     // impl.
-    struct SequenceOrMap<Map>(PhantomData<fn() -> Map>);
+    struct SequenceOrMap<V>(PhantomData<fn() -> V>);
 
-    impl<'de> Visitor<'de> for SequenceOrMap<Map> {
-        type Value = Map;
+    impl<'de, V> Visitor<'de> for SequenceOrMap<V>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = HashMap<String, V>;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
             formatter.write_str("sequence or map")
@@ -147,6 +195,13 @@ where
             Ok(HashMap::new())
         }
 
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(HashMap::new())
+        }
+
         fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
         where
             M: MapAccess<'de>,
@@ -162,6 +217,32 @@ where
     deserializer.deserialize_any(SequenceOrMap(PhantomData))
 }
 
+/// Volumes of `/manga/{id}/aggregate`, keyed by volume number or "none"
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Aggregate {
+    pub result: String,
+    #[serde(deserialize_with = "sequence_or_map")]
+    pub volumes: HashMap<String, AggregateVolume>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AggregateVolume {
+    pub volume: String,
+    pub count: i64,
+    #[serde(deserialize_with = "sequence_or_map")]
+    pub chapters: HashMap<String, AggregateChapter>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AggregateChapter {
+    pub chapter: String,
+    pub id: String,
+    /// Uploads of the same chapter by other groups
+    #[serde(default)]
+    pub others: Vec<String>,
+    pub count: i64,
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct TagAttributes {
     #[serde(deserialize_with = "sequence_or_map")]
@@ -182,7 +263,9 @@ pub struct MangaAttributes {
     pub description: Map,
     #[serde(default = "bool::default")]
     pub is_locked: bool,
-    // pub links: Option<Map>,
+    /// Ids or urls on other sites, e.g. `al` for AniList and `mal` for MyAnimeList
+    #[serde(default, deserialize_with = "sequence_or_map")]
+    pub links: Map,
     pub original_language: String,
     pub last_volume: Option<String>,
     pub last_chapter: Option<String>,
@@ -214,11 +297,20 @@ pub struct CoverAttributes {
     pub volume: Option<String>,
     pub file_name: String,
     pub description: String,
+    pub locale: Option<String>,
     pub version: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomListAttributes {
+    pub name: String,
+    pub visibility: String,
+    pub version: i64,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScanlationGroupAttributes {
@@ -251,6 +343,7 @@ pub struct ChapterAttributes {
 }
 
 pub mod request {
+    use chrono::NaiveDate;
     use tanoshi_lib::prelude::{Input, InputType, TriState};
 
     use crate::filter::*;
@@ -272,13 +365,19 @@ pub mod request {
         pub excluded_tags_mode: Option<TagMode>,
         pub status: Vec<Status>,
         pub original_language: Vec<String>,
+        pub available_translated_language: Vec<String>,
         pub publication_demographic: Vec<Demographic>,
         pub ids: Vec<String>,
         pub content_rating: Vec<Rating>,
+        #[serde(serialize_with = "serialize_since")]
         pub created_at_since: Option<DateTime<Utc>>,
+        #[serde(serialize_with = "serialize_since")]
         pub updated_at_since: Option<DateTime<Utc>>,
         pub order: Option<ListOrder>,
         pub includes: Vec<String>,
+        /// Applied to the results, MangaDex can not filter by rating
+        #[serde(skip)]
+        pub min_rating: Option<f64>,
     }
 
     impl Default for MangaList {
@@ -302,12 +401,14 @@ pub mod request {
                 excluded_tags_mode: Default::default(),
                 status: Default::default(),
                 original_language: Default::default(),
+                available_translated_language: Default::default(),
                 publication_demographic: Default::default(),
                 ids: Default::default(),
                 content_rating: Default::default(),
                 created_at_since: Default::default(),
                 updated_at_since: Default::default(),
                 order: Default::default(),
+                min_rating: Default::default(),
             }
         }
     }
@@ -330,6 +431,11 @@ pub mod request {
             let mut year = None;
             let mut artists = vec![];
             let mut authors = vec![];
+            let mut publication_demographic = vec![];
+            let mut original_language = vec![];
+            let mut updated_at_since = None;
+            let mut order = None;
+            let mut min_rating = None;
 
             for filter in filters {
                 if TITLE_FILTER.eq(&filter) {
@@ -339,50 +445,48 @@ pub mod request {
                 } else if AUTHOR_FILTER.eq(&filter) {
                     if let Input::Text { state, .. } = filter {
                         authors = state
-                            .map(|s| s.split(',').map(|s| s.to_string()).collect())
+                            .map(|s| {
+                                s.split(',')
+                                    .map(|s| s.trim().to_string())
+                                    .filter(|s| !s.is_empty())
+                                    .collect()
+                            })
                             .unwrap_or_default();
                     }
                 } else if ARTIST_FILTER.eq(&filter) {
                     if let Input::Text { state, .. } = filter {
                         artists = state
-                            .map(|s| s.split(',').map(|s| s.to_string()).collect())
+                            .map(|s| {
+                                s.split(',')
+                                    .map(|s| s.trim().to_string())
+                                    .filter(|s| !s.is_empty())
+                                    .collect()
+                            })
                             .unwrap_or_default();
                     }
                 } else if YEAR_FILTER.eq(&filter) {
                     if let Input::Text { state, .. } = filter {
                         year = state.and_then(|y| y.parse().ok());
                     }
-                } else if TAG_FILTERS.eq(&filter) {
+                } else if is_tag_filter(&filter) {
                     if let Input::Group { state, .. } = filter {
-                        included_tags = state
-                            .iter()
-                            .filter_map(|s| {
-                                if let Input::State { name, selected } = s {
-                                    (selected.unwrap_or_default() == TriState::Included)
-                                        .then(|| name.clone())
-                                        .and_then(|name| {
-                                            TAG_ID_MAP.get(&name).map(|id| id.to_string())
-                                        })
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-
-                        excluded_tags = state
-                            .iter()
-                            .filter_map(|s| {
-                                if let Input::State { name, selected } = s {
-                                    (selected.unwrap_or_default() == TriState::Excluded)
-                                        .then(|| name.clone())
-                                        .and_then(|name| {
-                                            TAG_ID_MAP.get(&name).map(|id| id.to_string())
-                                        })
-                                } else {
-                                    None
+                        for input in state {
+                            if let Input::State {
+                                name,
+                                selected: Some(selected),
+                            } = input
+                            {
+                                let id = match TAG_CACHE.tag_id(&name) {
+                                    Some(id) => id,
+                                    None => continue,
+                                };
+                                match selected {
+                                    TriState::Included => included_tags.push(id),
+                                    TriState::Excluded => excluded_tags.push(id),
+                                    _ => {}
                                 }
-                            })
-                            .collect();
+                            }
+                        }
                     }
                 } else if INCLUDED_TAGS_MODE.eq(&filter) {
                     if let Input::Select { values, state, .. } = filter {
@@ -423,6 +527,48 @@ pub mod request {
                             })
                             .collect();
                     }
+                } else if DEMOGRAPHIC_FILTER.eq(&filter) {
+                    if let Input::Group { state, .. } = filter {
+                        publication_demographic = state
+                            .iter()
+                            .filter_map(|input| match input {
+                                Input::Checkbox {
+                                    name,
+                                    state: Some(true),
+                                } => Demographic::from_str(name).ok(),
+                                _ => None,
+                            })
+                            .collect();
+                    }
+                } else if ORIGINAL_LANGUAGE_FILTER.eq(&filter) {
+                    if let Input::Group { state, .. } = filter {
+                        original_language = state
+                            .iter()
+                            .filter_map(|input| match input {
+                                Input::Checkbox {
+                                    name,
+                                    state: Some(true),
+                                } => Some(name.clone()),
+                                _ => None,
+                            })
+                            .collect();
+                    }
+                } else if UPDATED_SINCE_FILTER.eq(&filter) {
+                    if let Input::Text { state, .. } = filter {
+                        updated_at_since = state.and_then(|since| parse_since(&since));
+                    }
+                } else if MIN_RATING_FILTER.eq(&filter) {
+                    if let Input::Text { state, .. } = filter {
+                        min_rating = state.and_then(|rating| rating.trim().parse().ok());
+                    }
+                } else if SORT_FILTER.eq(&filter) {
+                    if let Input::Sort {
+                        selection: Some((index, asc)),
+                        ..
+                    } = filter
+                    {
+                        order = list_order(index, asc);
+                    }
                 } else if CONTENT_RATING_FILTER.eq(&filter) {
                     if let Input::Group { state, .. } = filter {
                         content_rating = state
@@ -454,11 +600,118 @@ pub mod request {
                 excluded_tags_mode,
                 status,
                 content_rating,
+                publication_demographic,
+                original_language,
+                updated_at_since,
+                order,
+                min_rating,
                 ..Default::default()
             }
         }
     }
 
+    /// A date as `YYYY-MM-DD` or RFC 3339
+    fn parse_since(since: &str) -> Option<DateTime<Utc>> {
+        let since = since.trim();
+        DateTime::parse_from_rfc3339(since)
+            .map(|since| since.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(since, "%Y-%m-%d")
+                    .ok()?
+                    .and_hms_opt(0, 0, 0)
+                    .map(|since| DateTime::from_utc(since, Utc))
+            })
+    }
+
+    /// `ListOrder` for an entry of `SORT_FILTER`
+    fn list_order(index: i64, asc: bool) -> Option<ListOrder> {
+        let direction = Some(if asc { Order::Asc } else { Order::Desc });
+        let order = match index {
+            0 => ListOrder {
+                relevance: direction,
+                ..Default::default()
+            },
+            1 => ListOrder {
+                rating: direction,
+                ..Default::default()
+            },
+            2 => ListOrder {
+                followed_count: direction,
+                ..Default::default()
+            },
+            3 => ListOrder {
+                latest_uploaded_chapter: direction,
+                ..Default::default()
+            },
+            4 => ListOrder {
+                created_at: direction,
+                ..Default::default()
+            },
+            5 => ListOrder {
+                title: direction,
+                ..Default::default()
+            },
+            6 => ListOrder {
+                year: direction,
+                ..Default::default()
+            },
+            _ => return None,
+        };
+
+        Some(order)
+    }
+
+    #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Aggregate {
+        pub translated_language: Vec<String>,
+    }
+
+    impl Aggregate {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
+
+    #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Statistics {
+        pub manga: Vec<String>,
+    }
+
+    impl Statistics {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
+
+    #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CoverList {
+        pub limit: i64,
+        pub manga: Vec<String>,
+    }
+
+    impl CoverList {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
+
+    #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AuthorList {
+        pub limit: i64,
+        pub name: Option<String>,
+    }
+
+    impl AuthorList {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
+
     #[derive(Debug, Clone, Default, Deserialize, Serialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Manga {
@@ -471,17 +724,27 @@ pub mod request {
         pub limit: i64,
         pub offset: i64,
         pub translated_language: Vec<String>,
+        pub content_rating: Vec<Rating>,
+        #[serde(serialize_with = "serialize_since")]
         pub created_at_since: Option<DateTime<Utc>>,
+        #[serde(serialize_with = "serialize_since")]
         pub updated_at_since: Option<DateTime<Utc>>,
+        #[serde(serialize_with = "serialize_since")]
         pub published_at_since: Option<DateTime<Utc>>,
         pub order: Option<ListOrder>,
         pub includes: Vec<String>,
     }
+
+    impl MangaFeed {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use tanoshi_lib::prelude::Input;
+    use tanoshi_lib::prelude::{Input, TriState};
 
     use super::request::MangaList;
 
@@ -536,4 +799,109 @@ mod test {
         let query = manga_list.to_query_string().unwrap();
         assert_eq!("limit=0&offset=0&status[0]=ongoing&status[1]=completed&status[2]=hiatus&status[3]=cancelled&contentRating[0]=safe&contentRating[1]=suggestive&contentRating[2]=erotica&contentRating[3]=pornographic&includes[0]=cover_art&includes[1]=author&includes[2]=artist&includes[3]=scanlation_group", query, "expected got {query}");
     }
+
+    #[test]
+    fn test_search_filters_to_manga_list_request() {
+        let input = vec![
+            Input::Group {
+                name: "Demographic".to_string(),
+                state: vec![
+                    Input::Checkbox {
+                        name: "shounen".to_string(),
+                        state: Some(false),
+                    },
+                    Input::Checkbox {
+                        name: "seinen".to_string(),
+                        state: Some(true),
+                    },
+                ],
+            },
+            Input::Group {
+                name: "Original Language".to_string(),
+                state: vec![Input::Checkbox {
+                    name: "ko".to_string(),
+                    state: Some(true),
+                }],
+            },
+            Input::Sort {
+                name: "Sort By".to_string(),
+                values: vec![],
+                selection: Some((1, false)),
+            },
+            Input::Text {
+                name: "Updated Since".to_string(),
+                state: Some("2026-10-01".to_string()),
+            },
+            Input::Text {
+                name: "Minimum Rating".to_string(),
+                state: Some(" 7.5".to_string()),
+            },
+        ];
+
+        let manga_list: MangaList = input.into();
+        assert_eq!(manga_list.min_rating, Some(7.5));
+        let query = manga_list.to_query_string().unwrap();
+        assert_eq!("limit=0&offset=0&originalLanguage[0]=ko&publicationDemographic[0]=seinen&updatedAtSince=2026-10-01T00%3A00%3A00&order[rating]=desc&includes[0]=cover_art&includes[1]=author&includes[2]=artist&includes[3]=scanlation_group", query, "expected got {query}");
+
+        let input = vec![
+            Input::Sort {
+                name: "Sort By".to_string(),
+                values: vec![],
+                selection: Some((5, true)),
+            },
+            Input::Text {
+                name: "Updated Since".to_string(),
+                state: Some("2026-10-01T12:30:00+02:00".to_string()),
+            },
+        ];
+
+        let manga_list: MangaList = input.into();
+        let query = manga_list.to_query_string().unwrap();
+        assert!(query.contains("updatedAtSince=2026-10-01T10%3A30%3A00&order[title]=asc"));
+    }
+
+    #[test]
+    fn test_grouped_tags_to_manga_list_request() {
+        let input = vec![
+            Input::Group {
+                name: "Genre".to_string(),
+                state: vec![
+                    Input::State {
+                        name: "Action".to_string(),
+                        selected: Some(TriState::Included),
+                    },
+                    Input::State {
+                        name: "Comedy".to_string(),
+                        selected: Some(TriState::Excluded),
+                    },
+                ],
+            },
+            Input::Group {
+                name: "Theme".to_string(),
+                state: vec![
+                    Input::State {
+                        name: "School Life".to_string(),
+                        selected: Some(TriState::Included),
+                    },
+                    Input::State {
+                        name: "Unknown Tag".to_string(),
+                        selected: Some(TriState::Included),
+                    },
+                ],
+            },
+        ];
+
+        let manga_list: MangaList = input.into();
+        assert_eq!(
+            manga_list.included_tags,
+            vec![
+                "391b0423-d847-456f-aff0-8b0cfc03066b",
+                "caaa44eb-cd40-4177-b930-79d3ef2afe87"
+            ]
+        );
+        assert_eq!(
+            manga_list.excluded_tags,
+            vec!["4d32cc48-9f00-4cca-9b5a-a839f0764984"]
+        );
+    }
 }
@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 use self::manga::{
-    AuthorAttributes, ChapterAttributes, CoverAttributes, MangaAttributes,
+    AuthorAttributes, ChapterAttributes, CoverAttributes, CustomListAttributes, MangaAttributes,
     ScanlationGroupAttributes, TagAttributes,
 };
 
@@ -49,12 +51,32 @@ pub enum Relationship {
     },
     CustomList {
         id: String,
+        attributes: Option<CustomListAttributes>,
+        #[serde(default)]
+        relationships: Vec<Relationship>,
     },
     Creator {
         id: String,
     },
 }
 
+impl Relationship {
+    pub fn id(&self) -> &str {
+        match self {
+            Relationship::Manga { id, .. }
+            | Relationship::Chapter { id, .. }
+            | Relationship::CoverArt { id, .. }
+            | Relationship::Author { id, .. }
+            | Relationship::Artist { id, .. }
+            | Relationship::ScanlationGroup { id, .. }
+            | Relationship::Tag { id, .. }
+            | Relationship::User { id }
+            | Relationship::CustomList { id, .. }
+            | Relationship::Creator { id } => id,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Home {
@@ -85,6 +107,46 @@ pub struct AtHomeChapter {
     pub data_saver: Vec<String>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ResultsStatistics {
+    pub result: String,
+    pub statistics: HashMap<String, MangaStatistics>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MangaStatistics {
+    pub rating: Option<StatisticsRating>,
+    pub follows: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StatisticsRating {
+    pub average: Option<f64>,
+    pub bayesian: Option<f64>,
+}
+
+impl MangaStatistics {
+    /// Bayesian rating out of 10, `None` when nobody rated the manga yet
+    pub fn rating(&self) -> Option<f64> {
+        let rating = self.rating.as_ref()?;
+        rating
+            .bayesian
+            .or(rating.average)
+            .filter(|rating| *rating > 0.0)
+    }
+}
+
+/// Outcome of an image fetch from a MangaDex@Home node
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AtHomeReport {
+    pub url: String,
+    pub success: bool,
+    pub cached: bool,
+    pub bytes: usize,
+    /// Milliseconds
+    pub duration: u128,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
@@ -1,7 +1,24 @@
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
 use lazy_static::lazy_static;
 use phf::phf_map;
 use tanoshi_lib::prelude::{Input, InputType};
 
+use crate::dto::Relationship;
+use crate::preferences::LANGUAGES;
+
+/// How long tags fetched from `/manga/tag` are used before fetching them again
+pub const TAG_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a failed fetch of `/manga/tag` is remembered before trying again
+pub const TAG_RETRY: Duration = Duration::from_secs(5 * 60);
+
+/// Tag groups shown in the filter list, in order. Tags of other groups are
+/// left out since their filters could not be told apart from other groups
+static TAG_GROUPS: &[&str] = &["genre", "theme", "format", "content"];
+
 lazy_static! {
     pub static ref TITLE_FILTER: Input = Input::Text {
         name: "Title".to_string(),
@@ -386,6 +403,54 @@ lazy_static! {
             },
         ]
     };
+    pub static ref DEMOGRAPHIC_FILTER: Input = Input::Group {
+        name: "Demographic".to_string(),
+        state: ["shounen", "shoujo", "josei", "seinen", "none"]
+            .iter()
+            .map(|demographic| Input::Checkbox {
+                name: demographic.to_string(),
+                state: Some(false),
+            })
+            .collect(),
+    };
+    pub static ref ORIGINAL_LANGUAGE_FILTER: Input = Input::Group {
+        name: "Original Language".to_string(),
+        state: LANGUAGES
+            .iter()
+            .map(|lang| Input::Checkbox {
+                name: lang.to_string(),
+                state: Some(false),
+            })
+            .collect(),
+    };
+    pub static ref SORT_FILTER: Input = Input::Sort {
+        name: "Sort By".to_string(),
+        values: vec![
+            InputType::String("Relevance".to_string()),
+            InputType::String("Rating".to_string()),
+            InputType::String("Follows".to_string()),
+            InputType::String("Latest Upload".to_string()),
+            InputType::String("Created".to_string()),
+            InputType::String("Title".to_string()),
+            InputType::String("Year".to_string()),
+        ],
+        selection: None,
+    };
+    /// `YYYY-MM-DD`
+    pub static ref UPDATED_SINCE_FILTER: Input = Input::Text {
+        name: "Updated Since".to_string(),
+        state: None,
+    };
+    /// Bayesian rating out of 10, applied to each page of results
+    pub static ref MIN_RATING_FILTER: Input = Input::Text {
+        name: "Minimum Rating".to_string(),
+        state: None,
+    };
+    /// Url or id of an MDList, other filters are ignored when it is set
+    pub static ref CUSTOM_LIST_FILTER: Input = Input::Text {
+        name: "Custom List URL/ID".to_string(),
+        state: None,
+    };
     pub static ref FILTER_LIST: Vec<Input> = vec![
         TITLE_FILTER.clone(),
         AUTHOR_FILTER.clone(),
@@ -396,7 +461,14 @@ lazy_static! {
         EXCLUDED_TAGS_MODE.clone(),
         STATUS_FILTER.clone(),
         CONTENT_RATING_FILTER.clone(),
+        DEMOGRAPHIC_FILTER.clone(),
+        ORIGINAL_LANGUAGE_FILTER.clone(),
+        SORT_FILTER.clone(),
+        UPDATED_SINCE_FILTER.clone(),
+        MIN_RATING_FILTER.clone(),
+        CUSTOM_LIST_FILTER.clone(),
     ];
+    pub static ref TAG_CACHE: TagCache = TagCache::new(TAG_TTL, TAG_RETRY);
 }
 
 pub static TAG_ID_MAP: phf::Map<&'static str, &'static str> = phf_map! {
@@ -477,3 +549,257 @@ pub static TAG_ID_MAP: phf::Map<&'static str, &'static str> = phf_map! {
     "Tragedy" => "f8f62932-27da-4fe4-8ee1-6779a8c5edba",
     "Gyaru" => "fad12b5e-68ba-460e-b933-9ae8318f5b65",
 };
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub id: String,
+    pub name: String,
+    pub group: String,
+}
+
+pub fn map_result_to_tags(data: Vec<Relationship>) -> Vec<Tag> {
+    data.into_iter()
+        .filter_map(|data| match data {
+            Relationship::Tag {
+                id,
+                attributes: Some(attributes),
+                ..
+            } => Some(Tag {
+                id,
+                name: attributes
+                    .name
+                    .get("en")
+                    .or_else(|| attributes.name.values().next())?
+                    .clone(),
+                group: attributes.group,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Tags fetched from MangaDex, kept for `ttl`. A failed fetch is not retried
+/// for `retry`
+pub struct TagCache {
+    ttl: Duration,
+    retry: Duration,
+    tags: RwLock<Option<(Instant, Vec<Tag>)>>,
+    failed_at: RwLock<Option<Instant>>,
+}
+
+impl TagCache {
+    pub fn new(ttl: Duration, retry: Duration) -> Self {
+        Self {
+            ttl,
+            retry,
+            tags: RwLock::new(None),
+            failed_at: RwLock::new(None),
+        }
+    }
+
+    /// Cached tags, calling `fetch` once they are older than `ttl` and the last
+    /// failure is older than `retry`. Stale tags are still returned when
+    /// `fetch` fails
+    pub fn get_or_fetch<F>(&self, fetch: F) -> Option<Vec<Tag>>
+    where
+        F: FnOnce() -> Result<Vec<Tag>>,
+    {
+        if let Some((fetched_at, tags)) = self.tags.read().ok()?.as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Some(tags.clone());
+            }
+        }
+
+        let failed_recently = self
+            .failed_at
+            .read()
+            .ok()?
+            .map(|failed_at| failed_at.elapsed() < self.retry)
+            .unwrap_or(false);
+        let fetched = if failed_recently { None } else { Some(fetch()) };
+
+        match fetched {
+            Some(Ok(tags)) if !tags.is_empty() => {
+                if let Ok(mut cache) = self.tags.write() {
+                    *cache = Some((Instant::now(), tags.clone()));
+                }
+                Some(tags)
+            }
+            fetched => {
+                if fetched.is_some() {
+                    if let Ok(mut failed_at) = self.failed_at.write() {
+                        *failed_at = Some(Instant::now());
+                    }
+                }
+                self.tags
+                    .read()
+                    .ok()?
+                    .as_ref()
+                    .map(|(_, tags)| tags.clone())
+            }
+        }
+    }
+
+    /// Id of the tag named `name`, `TAG_ID_MAP` is used when tags were never fetched
+    pub fn tag_id(&self, name: &str) -> Option<String> {
+        if let Some((_, tags)) = self.tags.read().ok()?.as_ref() {
+            if let Some(tag) = tags.iter().find(|tag| tag.name == name) {
+                return Some(tag.id.clone());
+            }
+        }
+
+        TAG_ID_MAP.get(name).map(|id| id.to_string())
+    }
+}
+
+fn tag_group_name(group: &str) -> String {
+    let mut chars = group.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// One group of tri-state tag inputs per tag group of `TAG_GROUPS`, e.g.
+/// "Genre" or "Theme"
+pub fn tag_filters(tags: &[Tag]) -> Vec<Input> {
+    TAG_GROUPS
+        .iter()
+        .filter_map(|group| {
+            let mut names: Vec<&str> = tags
+                .iter()
+                .filter(|tag| tag.group == *group)
+                .map(|tag| tag.name.as_str())
+                .collect();
+            if names.is_empty() {
+                return None;
+            }
+            names.sort_unstable();
+
+            Some(Input::Group {
+                name: tag_group_name(group),
+                state: names
+                    .into_iter()
+                    .map(|name| Input::State {
+                        name: name.to_string(),
+                        selected: None,
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// `FILTER_LIST` with the static `TAG_FILTERS` replaced by `tags` when available
+pub fn filter_list(tags: Option<&[Tag]>) -> Vec<Input> {
+    match tags {
+        Some(tags) if !tags.is_empty() => FILTER_LIST
+            .iter()
+            .flat_map(|filter| {
+                if TAG_FILTERS.eq(filter) {
+                    tag_filters(tags)
+                } else {
+                    vec![filter.clone()]
+                }
+            })
+            .collect(),
+        _ => FILTER_LIST.clone(),
+    }
+}
+
+/// Tag filters are `TAG_FILTERS` or one of `tag_filters`, told apart by name
+pub fn is_tag_filter(filter: &Input) -> bool {
+    match filter {
+        Input::Group { name, .. } => {
+            TAG_FILTERS.eq(filter)
+                || TAG_GROUPS
+                    .iter()
+                    .any(|group| tag_group_name(group) == *name)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::dto::{Data, Results};
+
+    fn tags() -> Vec<Tag> {
+        let res: Results = serde_json::from_str(include_str!("../fixtures/tags.json")).unwrap();
+        match res.data {
+            Data::Multiple { data, .. } => map_result_to_tags(data),
+            _ => panic!("expected a collection"),
+        }
+    }
+
+    fn names(input: &Input) -> Vec<String> {
+        match input {
+            Input::Group { state, .. } => state.iter().map(|input| input.name()).collect(),
+            _ => panic!("expected a group"),
+        }
+    }
+
+    #[test]
+    fn test_tag_filters_grouped() {
+        let filters = tag_filters(&tags());
+        assert_eq!(
+            filters.iter().map(|f| f.name()).collect::<Vec<_>>(),
+            vec!["Genre", "Theme", "Format", "Content"]
+        );
+        assert_eq!(
+            names(&filters[0]),
+            vec!["Action", "Comedy", "Slice of Life"]
+        );
+        assert_eq!(names(&filters[1]), vec!["Found Family", "School Life"]);
+        assert!(filters.iter().all(is_tag_filter));
+
+        let list = filter_list(Some(&tags()));
+        assert!(!list.iter().any(|filter| TAG_FILTERS.eq(filter)));
+        assert_eq!(list.len(), FILTER_LIST.len() + 3);
+        assert!(!is_tag_filter(&STATUS_FILTER));
+        assert!(!is_tag_filter(&Input::Group {
+            name: "Status".to_string(),
+            state: vec![Input::State {
+                name: "Ongoing".to_string(),
+                selected: None,
+            }],
+        }));
+
+        assert_eq!(filter_list(None).len(), FILTER_LIST.len());
+    }
+
+    #[test]
+    fn test_tag_cache_ttl() {
+        let cache = TagCache::new(Duration::from_secs(60), Duration::ZERO);
+        assert_eq!(
+            cache.tag_id("Action"),
+            Some(TAG_ID_MAP["Action"].to_string())
+        );
+        assert_eq!(cache.tag_id("Found Family"), None);
+
+        assert_eq!(cache.get_or_fetch(|| Err(anyhow!("offline"))), None);
+        assert_eq!(cache.get_or_fetch(|| Ok(tags())).unwrap().len(), 7);
+        let cached = cache.get_or_fetch(|| panic!("tags should be cached"));
+        assert_eq!(cached.unwrap().len(), 7);
+        assert_eq!(
+            cache.tag_id("Found Family").as_deref(),
+            Some("0a6f0b3c-6b4e-4d2f-9f1e-2c3b4a5d6e7f")
+        );
+
+        let cache = TagCache::new(Duration::ZERO, Duration::ZERO);
+        cache.get_or_fetch(|| Ok(tags()));
+        let stale = cache.get_or_fetch(|| Err(anyhow!("offline")));
+        assert_eq!(stale.unwrap().len(), 7);
+
+        // a failure is not retried right away
+        let cache = TagCache::new(Duration::ZERO, Duration::from_secs(60));
+        assert_eq!(cache.get_or_fetch(|| Err(anyhow!("offline"))), None);
+        assert_eq!(
+            cache.get_or_fetch(|| panic!("failure should be cached")),
+            None
+        );
+    }
+}
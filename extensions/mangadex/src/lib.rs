@@ -1,18 +1,26 @@
+mod auth;
+mod bbcode;
+mod chapter;
 mod dto;
 mod filter;
+mod metadata;
+mod preferences;
 
 use crate::dto::{
-    manga::{request, ListOrder, Order, Rating},
+    manga::{request, Aggregate, ListOrder, Map, MangaAttributes, Order, Rating},
     Relationship, Results,
 };
 use anyhow::{anyhow, bail, Result};
-use dto::ResultsAtHome;
-use fancy_regex::Regex;
+use log::warn;
+use dto::{AtHomeReport, MangaStatistics, ResultsAtHome, ResultsStatistics};
 use tanoshi_lib::prelude::*;
 use tanoshi_lib::extensions::PluginRegistrar;
-use lazy_static::lazy_static;
 use networking::{Agent, build_ureq_agent};
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
 tanoshi_lib::export_plugin!(register);
 
@@ -20,43 +28,85 @@ fn register(registrar: &mut dyn PluginRegistrar) {
     registrar.register_function(Box::new(Mangadex::default()));
 }
 
-lazy_static! {
-    static ref PREFERENCES: Vec<Input> = vec![];
-}
-
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub static ID: i64 = 2;
 pub static NAME: &str = "Mangadex";
 pub static URL: &str = "https://api.mangadex.org";
+pub static REPORT_URL: &str = "https://api.mangadex.network/report";
+
+/// Maximum page size of the chapter feed
+const FEED_LIMIT: i64 = 500;
+/// MangaDex does not serve results past `offset + limit` of 10000
+const MAX_OFFSET: i64 = 10000;
+/// Manga per page of a listing
+const PAGE_LIMIT: i64 = 20;
+/// Maximum page size of the cover list
+const COVER_LIMIT: i64 = 100;
+/// Chapters of the chapter feed read per page of latest manga
+const LATEST_LIMIT: i64 = 100;
+/// Maximum number of authors a name expands to
+const AUTHOR_LIMIT: i64 = 10;
+/// Most listing requests made to fill one page of `fill_page`
+const MAX_FILL_REQUESTS: usize = 5;
+/// Delay between consecutive requests of a paginated walk, MangaDex allows
+/// about 5 requests per second
+const RATE_LIMIT: Duration = Duration::from_millis(250);
 
 pub struct Mangadex {
     preferences: Vec<Input>,
     client: Agent,
+    auth: auth::Auth,
+    page_offsets: Mutex<PageOffsets>,
+    latest_cursor: Mutex<Option<LatestCursor>>,
+}
+
+/// Listing offset each page of the last rating filtered query starts at,
+/// another query replaces them
+#[derive(Debug, Default)]
+struct PageOffsets {
+    query: String,
+    offsets: HashMap<i64, i64>,
+}
+
+impl PageOffsets {
+    fn get(&self, query: &str, page: i64) -> Option<i64> {
+        (self.query == query)
+            .then(|| self.offsets.get(&page).copied())
+            .flatten()
+    }
+
+    fn insert(&mut self, query: &str, page: i64, offset: i64) {
+        if self.query != query {
+            self.query = query.to_string();
+            self.offsets.clear();
+        }
+        self.offsets.insert(page, offset);
+    }
+}
+
+/// Where the next page of latest manga continues in the chapter feed
+struct LatestCursor {
+    /// Feed and query the cursor belongs to
+    key: String,
+    page: i64,
+    offset: i64,
+    /// Manga listed on earlier pages
+    seen: HashSet<String>,
 }
 
 impl Default for Mangadex {
     fn default() -> Self {
         Self {
-            preferences: PREFERENCES.clone(),
+            preferences: preferences::PREFERENCES.clone(),
             client: build_ureq_agent(None, None),
+            auth: auth::Auth::default(),
+            page_offsets: Mutex::new(PageOffsets::default()),
+            latest_cursor: Mutex::new(None),
         }
     }
 }
 
-#[must_use]
-fn remove_bbcode(string: String) -> String {
-    let regex = Regex::new(r#"\[(\w+)[^]]*](.*?)\[/\1]"#).unwrap();
-
-    let result = string
-        .replace("[list]", "")
-        .replace("[/list]", "")
-        .replace("[*]", "")
-        .replace("[hr]", "\n");
-
-    regex.replace_all(&result, "$2").to_string()
-}
-
 pub fn map_tags_to_string(relationships: Vec<Relationship>) -> Vec<String> {
     let mut tags = vec![];
     for relationship in relationships {
@@ -70,7 +120,32 @@ pub fn map_tags_to_string(relationships: Vec<Relationship>) -> Vec<String> {
     tags
 }
 
-pub fn map_result_to_manga(data: Relationship) -> Option<MangaInfo> {
+/// Pick the entry in `lang`, falling back to english, romanized japanese,
+/// japanese and then whatever is available
+fn localized(map: &Map, lang: &str) -> Option<String> {
+    [lang, "en", "ja-ro", "ja"]
+        .iter()
+        .find_map(|lang| map.get(*lang))
+        .or_else(|| map.values().next())
+        .cloned()
+}
+
+fn manga_title(attr: &MangaAttributes, lang: &str) -> Option<String> {
+    attr.title
+        .get(lang)
+        .or_else(|| attr.alt_titles.iter().find_map(|title| title.get(lang)))
+        .cloned()
+        .or_else(|| localized(&attr.title, lang))
+}
+
+fn cover_url(manga_id: &str, file_name: &str) -> String {
+    format!(
+        "https://uploads.mangadex.org/covers/{}/{}",
+        manga_id, file_name
+    )
+}
+
+pub fn map_result_to_manga(data: Relationship, lang: &str) -> Option<MangaInfo> {
     match data {
         Relationship::Manga {
             id,
@@ -79,7 +154,7 @@ pub fn map_result_to_manga(data: Relationship) -> Option<MangaInfo> {
         } => {
             let mut author = vec![];
             let mut genre = vec![];
-            let mut file_name = "".to_string();
+            let mut covers = vec![];
             for relationship in relationships {
                 match relationship {
                     Relationship::Author { attributes, .. } => {
@@ -98,31 +173,41 @@ pub fn map_result_to_manga(data: Relationship) -> Option<MangaInfo> {
                             genre.push(name.to_owned());
                         }
                     }
-                    Relationship::CoverArt { attributes, .. } => {
-                        if let Some(name) = attributes.map(|attr| attr.file_name) {
-                            file_name = name;
-                        }
-                    }
+                    Relationship::CoverArt {
+                        attributes: Some(attributes),
+                        ..
+                    } => covers.push(attributes),
                     _ => {}
                 };
             }
 
+            let title = attributes
+                .as_ref()
+                .and_then(|attr| manga_title(attr, lang))
+                .unwrap_or_default();
+            let original_language = attributes
+                .as_ref()
+                .map(|attr| attr.original_language.as_str())
+                .unwrap_or_default();
+            let file_name = metadata::pick_cover(&covers, original_language)
+                .map(|cover| cover.file_name.as_str())
+                .unwrap_or_default();
+            let cover_url = cover_url(&id, file_name);
+
+            let mut description = vec![];
+            if let Some(attr) = attributes.as_ref() {
+                if let Some(text) = localized(&attr.description, lang) {
+                    description.push(bbcode::to_markdown(&text));
+                }
+                let lines = metadata::describe(attr, &title);
+                if !lines.is_empty() {
+                    description.push(lines.join("\n"));
+                }
+            }
+
             Some(MangaInfo {
                 source_id: ID,
-                title: attributes
-                    .clone()
-                    .and_then(|attr| {
-                        if let Some(title) = attr.title.get("en").cloned() {
-                            Some(title)
-                        } else if let Some(title) = attr.title.get("ja-ro").cloned() {
-                            Some(title)
-                        } else if let Some(title) = attr.title.get("ja").cloned() {
-                            Some(title)
-                        } else {
-                            attr.title.values().next().cloned()
-                        }
-                    })
-                    .unwrap_or_else(String::new),
+                title,
                 author,
                 genre: attributes
                     .clone()
@@ -133,11 +218,9 @@ pub fn map_result_to_manga(data: Relationship) -> Option<MangaInfo> {
                     .clone()
                     .and_then(|attr| attr.status)
                     .map(|s| s.to_string()),
-                description: attributes
-                    .and_then(|attr| attr.description.get("en").cloned())
-                    .map(remove_bbcode),
+                description: (!description.is_empty()).then(|| description.join("\n\n")),
                 path: format!("/manga/{}", id),
-                cover_url: format!("https://uploads.mangadex.org/covers/{}/{}", id, file_name),
+                cover_url,
             })
         }
         _ => None,
@@ -145,6 +228,7 @@ pub fn map_result_to_manga(data: Relationship) -> Option<MangaInfo> {
 }
 
 pub fn map_result_to_chapter(data: Relationship) -> Option<ChapterInfo> {
+    let external = chapter::external_url(&data).is_some();
     match data {
         Relationship::Chapter {
             id,
@@ -167,14 +251,22 @@ pub fn map_result_to_chapter(data: Relationship) -> Option<ChapterInfo> {
                 .and_then(|attr| attr.title)
                 .unwrap_or_else(|| "".to_string());
 
-            if title.is_empty() {
-                if let Some(vol) = volume {
-                    title = format!("Volume {}", vol);
-                }
-                if let Some(ch) = number.clone() {
-                    title = format!("{} Chapter {}", title, ch)
-                }
-                title = title.trim().to_string();
+            let mut label = vec![];
+            if let Some(vol) = volume.filter(|vol| !vol.is_empty()) {
+                label.push(format!("Vol. {}", vol));
+            }
+            if let Some(ch) = number.clone().filter(|ch| !ch.is_empty()) {
+                label.push(format!("Ch. {}", ch));
+            }
+            let label = label.join(" ");
+            title = match (label.is_empty(), title.trim().is_empty()) {
+                (false, false) => format!("{} - {}", label, title.trim()),
+                (false, true) => label,
+                (true, false) => title.trim().to_string(),
+                (true, true) => "Oneshot".to_string(),
+            };
+            if external {
+                title = format!("[External] {}", title);
             }
 
             Some(ChapterInfo {
@@ -194,35 +286,685 @@ pub fn map_result_to_chapter(data: Relationship) -> Option<ChapterInfo> {
     }
 }
 
-pub fn map_result_to_pages(data: ResultsAtHome) -> Vec<String> {
-    data.chapter
-        .data
+pub fn map_result_to_pages(data: ResultsAtHome, data_saver: bool) -> Vec<String> {
+    let (quality, files) = if data_saver {
+        ("data-saver", &data.chapter.data_saver)
+    } else {
+        ("data", &data.chapter.data)
+    };
+
+    files
         .iter()
-        .map(|d| format!("{}/data/{}/{}", data.base_url, data.chapter.hash, d))
+        .map(|d| format!("{}/{}/{}/{}", data.base_url, quality, data.chapter.hash, d))
         .collect()
 }
 
+fn at_home_url(chapter_id: &str, force_port_443: bool) -> String {
+    if force_port_443 {
+        format!("{}/at-home/server/{}?forcePort443=true", URL, chapter_id)
+    } else {
+        format!("{}/at-home/server/{}", URL, chapter_id)
+    }
+}
+
+/// Images served from mangadex.org itself are not MangaDex@Home nodes and
+/// must not be reported
+fn should_report(url: &str) -> bool {
+    !url.contains(".mangadex.org/")
+}
+
+/// Whether `s` looks like a MangaDex id
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Author ids for a list of author names or ids. Names are looked up with
+/// `search`, exact matches are preferred and otherwise every match is kept.
+/// Names without any match are skipped
+pub fn resolve_authors<F>(names: Vec<String>, mut search: F) -> Result<Vec<String>>
+where
+    F: FnMut(&str) -> Result<Vec<Relationship>>,
+{
+    let mut ids = vec![];
+    for name in names {
+        if is_uuid(&name) {
+            ids.push(name);
+            continue;
+        }
+
+        let authors: Vec<(String, String)> = search(&name)?
+            .into_iter()
+            .filter_map(|author| match author {
+                Relationship::Author {
+                    id,
+                    attributes: Some(attributes),
+                }
+                | Relationship::Artist {
+                    id,
+                    attributes: Some(attributes),
+                } => Some((id, attributes.name)),
+                _ => None,
+            })
+            .collect();
+        if authors.is_empty() {
+            warn!("no author named {}", name);
+            continue;
+        }
+
+        let exact: Vec<String> = authors
+            .iter()
+            .filter(|(_, author)| author.eq_ignore_ascii_case(&name))
+            .map(|(id, _)| id.clone())
+            .collect();
+        if exact.is_empty() {
+            ids.extend(authors.into_iter().map(|(id, _)| id));
+        } else {
+            ids.extend(exact);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+
+    Ok(ids)
+}
+
+/// Ratings of a filter restricted to the allowed ones, every allowed rating
+/// when the filter has none and `None` when nothing is left to search
+fn allowed_ratings(requested: &[Rating], allowed: &[Rating]) -> Option<Vec<Rating>> {
+    if requested.is_empty() {
+        return Some(allowed.to_vec());
+    }
+
+    let ratings: Vec<Rating> = requested
+        .iter()
+        .filter(|rating| allowed.contains(rating))
+        .cloned()
+        .collect();
+    if ratings.is_empty() {
+        None
+    } else {
+        Some(ratings)
+    }
+}
+
+fn manga_id(manga: &MangaInfo) -> &str {
+    manga.path.trim_start_matches("/manga/")
+}
+
+/// Fold rating and follows into the description and drop manga rated below
+/// `min_rating`, manga without statistics are kept
+pub fn apply_statistics(
+    manga: Vec<MangaInfo>,
+    statistics: &HashMap<String, MangaStatistics>,
+    min_rating: Option<f64>,
+) -> Vec<MangaInfo> {
+    manga
+        .into_iter()
+        .filter_map(|mut manga| {
+            let stats = match statistics.get(manga_id(&manga)) {
+                Some(stats) => stats,
+                None => return Some(manga),
+            };
+            if let Some(min_rating) = min_rating {
+                if stats.rating().unwrap_or_default() < min_rating {
+                    return None;
+                }
+            }
+
+            let lines = metadata::describe_statistics(stats);
+            if !lines.is_empty() {
+                let lines = lines.join("\n");
+                manga.description = Some(match manga.description {
+                    Some(description) => format!("{}\n{}", description, lines),
+                    None => lines,
+                });
+            }
+
+            Some(manga)
+        })
+        .collect()
+}
+
+/// Entries kept from one page of a listing
+pub struct ListingPage<T> {
+    pub items: Vec<T>,
+    /// Number of entries the page listed before any were dropped
+    pub listed: i64,
+    pub total: i64,
+}
+
+/// Read listing pages of `limit` from `start` on until `PAGE_LIMIT` entries
+/// are kept, for filters MangaDex can not apply such as the minimum rating.
+/// The last page is kept whole, so a page can hold more than `PAGE_LIMIT`
+/// entries. Returns the entries and the offset the next page starts at
+pub fn fill_page<T, F>(start: i64, limit: i64, mut fetch: F) -> Result<(Vec<T>, i64)>
+where
+    F: FnMut(i64) -> Result<ListingPage<T>>,
+{
+    let mut items = vec![];
+    let mut offset = start;
+    for _ in 0..MAX_FILL_REQUESTS {
+        let page = fetch(offset)?;
+        items.extend(page.items);
+        offset += page.listed;
+        if items.len() as i64 >= PAGE_LIMIT
+            || page.listed == 0
+            || offset >= page.total
+            || offset + limit > MAX_OFFSET
+        {
+            break;
+        }
+    }
+
+    Ok((items, offset))
+}
+
+/// Number chapters by `numbers` of `chapter::chapter_numbers`, newest first
+pub fn order_chapters(
+    mut chapters: Vec<ChapterInfo>,
+    numbers: &HashMap<String, f64>,
+) -> Vec<ChapterInfo> {
+    for chapter in chapters.iter_mut() {
+        if let Some(number) = numbers.get(chapter.path.trim_start_matches("/chapter/")) {
+            chapter.number = *number;
+        }
+    }
+    chapters.sort_by(|a, b| b.number.total_cmp(&a.number));
+
+    chapters
+}
+
+/// Id of an MDList from its url, e.g. `https://mangadex.org/list/{id}/name`, or
+/// the id itself
+pub fn custom_list_id(input: &str) -> Option<String> {
+    input
+        .trim()
+        .split(['/', '?', '#'])
+        .find(|segment| is_uuid(segment))
+        .map(str::to_string)
+}
+
+/// Ids of the manga of a custom list on `page`
+pub fn custom_list_page(list: &Relationship, page: i64) -> Vec<String> {
+    let relationships = match list {
+        Relationship::CustomList { relationships, .. } => relationships,
+        _ => return vec![],
+    };
+
+    relationships
+        .iter()
+        .filter(|relationship| matches!(relationship, Relationship::Manga { .. }))
+        .skip(((page.max(1) - 1) * PAGE_LIMIT) as usize)
+        .take(PAGE_LIMIT as usize)
+        .map(|manga| manga.id().to_string())
+        .collect()
+}
+
+/// Ids of the manga of `chapters` not in `seen`, in feed order. The ids are
+/// added to `seen`
+pub fn feed_manga_ids(chapters: &[Relationship], seen: &mut HashSet<String>) -> Vec<String> {
+    chapters
+        .iter()
+        .filter_map(|chapter| match chapter {
+            Relationship::Chapter { relationships, .. } => {
+                relationships
+                    .iter()
+                    .find_map(|relationship| match relationship {
+                        Relationship::Manga { id, .. } => Some(id.clone()),
+                        _ => None,
+                    })
+            }
+            _ => None,
+        })
+        .filter(|id| seen.insert(id.clone()))
+        .collect()
+}
+
+/// Walk a paginated collection until `total` is reached, `fetch` is called
+/// with the offset of each page. Entries are de-duplicated by id since the
+/// collection can shift between requests.
+pub fn fetch_all<F>(limit: i64, mut fetch: F) -> Result<Vec<Relationship>>
+where
+    F: FnMut(i64) -> Result<Results>,
+{
+    let mut ids = HashSet::new();
+    let mut items = vec![];
+    let mut offset = 0;
+    loop {
+        let res = fetch(offset)?;
+        let (data, total) = if let dto::Data::Multiple { data, total, .. } = res.data {
+            (data, total)
+        } else {
+            bail!("invalid data");
+        };
+
+        let count = data.len() as i64;
+        for item in data {
+            if ids.insert(item.id().to_string()) {
+                items.push(item);
+            }
+        }
+
+        offset += limit;
+        if count == 0 || offset >= total || offset + limit > MAX_OFFSET {
+            break;
+        }
+    }
+
+    Ok(items)
+}
+
+/// Every chapter of the feed at `url` matching `query`, requested
+/// `FEED_LIMIT` at a time with `RATE_LIMIT` between requests
+fn fetch_feed(client: &Agent, url: &str, query: request::MangaFeed) -> Result<Vec<Relationship>> {
+    fetch_all(FEED_LIMIT, |offset| {
+        if offset > 0 {
+            thread::sleep(RATE_LIMIT);
+        }
+
+        let query = request::MangaFeed {
+            limit: FEED_LIMIT,
+            offset,
+            ..query.clone()
+        };
+        let url = format!("{}?{}", url, query.to_query_string()?);
+
+        Ok(client.get(&url).call()?.into_json()?)
+    })
+}
+
 impl Mangadex {
-    fn get_manga_list(&self, mut page: i64, query: request::MangaList) -> Result<Vec<MangaInfo>> {
+    fn chapter_languages(&self) -> Vec<String> {
+        preferences::chapter_languages(&self.preferences)
+    }
+
+    fn preferred_language(&self) -> String {
+        preferences::preferred_language(&self.preferences)
+    }
+
+    fn content_ratings(&self) -> Vec<Rating> {
+        preferences::content_ratings(&self.preferences)
+    }
+
+    fn group_filter(&self) -> chapter::GroupFilter {
+        chapter::GroupFilter {
+            preferred: preferences::preferred_groups(&self.preferences),
+            blocked: preferences::blocked_groups(&self.preferences),
+            one_per_number: preferences::one_chapter_per_number(&self.preferences),
+        }
+    }
+
+    /// Fails with `chapter::ExternalChapter` when the chapter is hosted on another site
+    fn check_external(&self, chapter_id: &str) -> Result<()> {
+        let url = format!("{}/chapter/{}", URL, chapter_id);
+
+        let res: Results = self.client.get(&url).call()?.into_json()?;
+        if let dto::Data::Single { data } = res.data {
+            if let Some(url) = chapter::external_url(&data) {
+                bail!(chapter::ExternalChapter {
+                    url: url.to_string()
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_at_home(&self, chapter_id: &str, force_port_443: bool) -> Result<ResultsAtHome> {
+        let url = at_home_url(chapter_id, force_port_443);
+        Ok(self.client.get(&url).call()?.into_json()?)
+    }
+
+    /// Outcome of a HEAD request for an image, the image itself is loaded
+    /// later by the reader. `bytes` is the `Content-Length` of the response
+    /// and `duration` the latency of the HEAD request, not of a full fetch
+    fn probe_page(&self, url: &str) -> AtHomeReport {
+        let start = Instant::now();
+        let (success, cached, bytes) = match self.client.head(url).call() {
+            Ok(res) => {
+                let cached = res
+                    .header("X-Cache")
+                    .map(|cache| cache.starts_with("HIT"))
+                    .unwrap_or(false);
+                let bytes = res
+                    .header("Content-Length")
+                    .and_then(|len| len.parse().ok())
+                    .unwrap_or_default();
+                (true, cached, bytes)
+            }
+            Err(_) => (false, false, 0),
+        };
+
+        AtHomeReport {
+            url: url.to_string(),
+            success,
+            cached,
+            bytes,
+            duration: start.elapsed().as_millis(),
+        }
+    }
+
+    /// Report the outcome of `probe_page` for `url` to MangaDex@Home
+    fn report_page(&self, url: &str) {
+        if !should_report(url) {
+            return;
+        }
+
+        if let Ok(report) = serde_json::to_value(self.probe_page(url)) {
+            let _ = self.client.post(REPORT_URL).send_json(report);
+        }
+    }
+
+    fn access_token(&self) -> Result<Option<String>> {
+        self.auth.access_token(|form| {
+            Ok(self
+                .client
+                .post(auth::AUTH_URL)
+                .send_form(form)?
+                .into_json()?)
+        })
+    }
+
+    /// The access token when logging in works, otherwise requests fall back to
+    /// anonymous ones
+    fn optional_access_token(&self) -> Option<String> {
+        self.access_token().unwrap_or_else(|e| {
+            warn!("failed to log in, continuing anonymously: {}", e);
+            None
+        })
+    }
+
+    fn get_manga_by_ids(&self, ids: &[String]) -> Result<Vec<MangaInfo>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = request::MangaList {
+            limit: ids.len() as i64,
+            ids: ids.to_vec(),
+            content_rating: self.content_ratings(),
+            ..Default::default()
+        };
+        let url = format!("{}/manga?{}", URL, query.to_query_string()?);
+
+        let res: Results = self.client.get(&url).call()?.into_json()?;
+        if let dto::Data::Multiple { data, .. } = res.data {
+            let lang = self.preferred_language();
+            let mut manga: Vec<MangaInfo> = data
+                .into_iter()
+                .filter_map(|data| map_result_to_manga(data, &lang))
+                .collect();
+            manga.sort_by_key(|manga| ids.iter().position(|id| id == manga_id(manga)));
+            Ok(self.with_statistics(manga, None))
+        } else {
+            bail!("invalid data");
+        }
+    }
+
+    /// Manga of the latest chapters, from the feed of followed manga when
+    /// logged in and from every chapter otherwise. Pages continue where the
+    /// previous one stopped in the feed and leave out manga already listed
+    fn get_latest_updates(
+        &self,
+        mut page: i64,
+        access_token: Option<&str>,
+    ) -> Result<Vec<MangaInfo>> {
         if page < 1 {
             page = 1;
         }
-        let offset = (page - 1) * 20;
-        let query = request::MangaList {
-            limit: 20,
-            offset,
-            ..query
+        let query = request::MangaFeed {
+            limit: LATEST_LIMIT,
+            translated_language: self.chapter_languages(),
+            content_rating: self.content_ratings(),
+            order: Some(ListOrder {
+                readable_at: Some(Order::Desc),
+                ..Default::default()
+            }),
+            ..Default::default()
         };
+        let url = match access_token {
+            Some(_) => format!("{}/user/follows/manga/feed", URL),
+            None => format!("{}/chapter", URL),
+        };
+        let key = format!("{}?{}", url, query.to_query_string()?);
 
-        let url = format!("{}/manga?{}", URL, query.to_query_string()?);
+        let cursor = self
+            .latest_cursor
+            .lock()
+            .ok()
+            .and_then(|mut cursor| cursor.take())
+            .filter(|cursor| page > 1 && cursor.key == key && cursor.page == page);
+        let (start, mut seen) = match cursor {
+            Some(cursor) => (cursor.offset, cursor.seen),
+            None => ((page - 1) * LATEST_LIMIT, HashSet::new()),
+        };
+
+        // ids are collected over the whole page and hydrated at once, the feed
+        // pages after the first ask for fewer chapters so the ids still fit in
+        // a single `/manga?ids[]=` request of at most `LATEST_LIMIT`
+        let mut collected = 0;
+        let (ids, next) = fill_page(start, LATEST_LIMIT, |offset| {
+            if offset > start {
+                thread::sleep(RATE_LIMIT);
+            }
+
+            let query = request::MangaFeed {
+                limit: LATEST_LIMIT - collected,
+                offset,
+                ..query.clone()
+            };
+            let mut req = self
+                .client
+                .get(&format!("{}?{}", url, query.to_query_string()?));
+            if let Some(access_token) = access_token {
+                req = req.set("Authorization", &format!("Bearer {}", access_token));
+            }
+
+            let res: Results = req.call()?.into_json()?;
+            if let dto::Data::Multiple { data, total, .. } = res.data {
+                let ids = feed_manga_ids(&data, &mut seen);
+                collected += ids.len() as i64;
+                Ok(ListingPage {
+                    items: ids,
+                    listed: data.len() as i64,
+                    total,
+                })
+            } else {
+                bail!("invalid data");
+            }
+        })?;
+        let manga = self.get_manga_by_ids(&ids)?;
+
+        if let Ok(mut cursor) = self.latest_cursor.lock() {
+            *cursor = Some(LatestCursor {
+                key,
+                page: page + 1,
+                offset: next,
+                seen,
+            });
+        }
+
+        Ok(manga)
+    }
+
+    /// Covers of every volume of a manga
+    fn get_covers(&self, manga_id: &str) -> Result<Vec<Relationship>> {
+        let query = request::CoverList {
+            limit: COVER_LIMIT,
+            manga: vec![manga_id.to_string()],
+        };
+        let url = format!("{}/cover?{}", URL, query.to_query_string()?);
+
+        let res: Results = self.client.get(&url).call()?.into_json()?;
+        if let dto::Data::Multiple { data, .. } = res.data {
+            Ok(data
+                .into_iter()
+                .filter(|cover| matches!(cover, Relationship::CoverArt { .. }))
+                .collect())
+        } else {
+            bail!("invalid data");
+        }
+    }
+
+    fn get_aggregate(&self, path: &str) -> Result<Aggregate> {
+        let query = request::Aggregate {
+            translated_language: self.chapter_languages(),
+        };
+        let url = format!("{}{}/aggregate?{}", URL, path, query.to_query_string()?);
+
+        Ok(self.client.get(&url).call()?.into_json()?)
+    }
+
+    /// Statistics of every manga in a single request
+    fn get_statistics(&self, manga: &[MangaInfo]) -> Result<HashMap<String, MangaStatistics>> {
+        if manga.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let query = request::Statistics {
+            manga: manga
+                .iter()
+                .map(|manga| manga_id(manga).to_string())
+                .collect(),
+        };
+        let url = format!("{}/statistics/manga?{}", URL, query.to_query_string()?);
+
+        let res: ResultsStatistics = self.client.get(&url).call()?.into_json()?;
+        Ok(res.statistics)
+    }
+
+    /// `apply_statistics` with statistics fetched for `manga`, listings still
+    /// work when statistics are unavailable
+    fn with_statistics(&self, manga: Vec<MangaInfo>, min_rating: Option<f64>) -> Vec<MangaInfo> {
+        match self.get_statistics(&manga) {
+            Ok(statistics) => apply_statistics(manga, &statistics, min_rating),
+            Err(_) => manga,
+        }
+    }
+
+    fn get_custom_list(&self, list_id: &str, page: i64) -> Result<Vec<MangaInfo>> {
+        let url = format!("{}/list/{}", URL, list_id);
+
+        // private lists are only visible to their owner
+        let mut req = self.client.get(&url);
+        if let Some(access_token) = self.optional_access_token() {
+            req = req.set("Authorization", &format!("Bearer {}", access_token));
+        }
+
+        let res: Results = req.call()?.into_json()?;
+        if let dto::Data::Single { data } = res.data {
+            self.get_manga_by_ids(&custom_list_page(&data, page))
+        } else {
+            bail!("invalid data");
+        }
+    }
+
+    fn search_authors(&self, name: &str) -> Result<Vec<Relationship>> {
+        let query = request::AuthorList {
+            limit: AUTHOR_LIMIT,
+            name: Some(name.to_string()),
+        };
+        let url = format!("{}/author?{}", URL, query.to_query_string()?);
+
+        let res: Results = self.client.get(&url).call()?.into_json()?;
+        if let dto::Data::Multiple { data, .. } = res.data {
+            Ok(data)
+        } else {
+            bail!("invalid data");
+        }
+    }
+
+    fn get_tags(&self) -> Result<Vec<filter::Tag>> {
+        let url = format!("{}/manga/tag", URL);
 
         let res: Results = self.client.get(&url).call()?.into_json()?;
         if let dto::Data::Multiple { data, .. } = res.data {
-            Ok(data.into_iter().filter_map(map_result_to_manga).collect())
+            Ok(filter::map_result_to_tags(data))
+        } else {
+            bail!("invalid data");
+        }
+    }
+
+    fn get_listing_page(&self, query: &request::MangaList) -> Result<ListingPage<MangaInfo>> {
+        let url = format!("{}/manga?{}", URL, query.to_query_string()?);
+
+        let res: Results = self.client.get(&url).call()?.into_json()?;
+        if let dto::Data::Multiple { data, total, .. } = res.data {
+            let listed = data.len() as i64;
+            let lang = self.preferred_language();
+            let manga = data
+                .into_iter()
+                .filter_map(|data| map_result_to_manga(data, &lang))
+                .collect();
+            Ok(ListingPage {
+                items: self.with_statistics(manga, query.min_rating),
+                listed,
+                total,
+            })
         } else {
             bail!("invalid data");
         }
     }
+
+    fn get_manga_list(&self, mut page: i64, query: request::MangaList) -> Result<Vec<MangaInfo>> {
+        if page < 1 {
+            page = 1;
+        }
+        let content_rating = match allowed_ratings(&query.content_rating, &self.content_ratings()) {
+            Some(content_rating) => content_rating,
+            None => return Ok(vec![]),
+        };
+        let offset = (page - 1) * PAGE_LIMIT;
+        let query = request::MangaList {
+            limit: PAGE_LIMIT,
+            offset,
+            available_translated_language: self.chapter_languages(),
+            content_rating,
+            ..query
+        };
+
+        let min_rating = match query.min_rating {
+            Some(min_rating) => min_rating,
+            None => return Ok(self.get_listing_page(&query)?.items),
+        };
+
+        // manga below the minimum rating leave gaps in the listing, so each
+        // page continues where the previous one stopped
+        let key = format!(
+            "{}&minRating={}",
+            request::MangaList {
+                offset: 0,
+                ..query.clone()
+            }
+            .to_query_string()?,
+            min_rating
+        );
+        let start = self
+            .page_offsets
+            .lock()
+            .ok()
+            .and_then(|offsets| offsets.get(&key, page))
+            .unwrap_or(offset);
+        let (manga, next) = fill_page(start, PAGE_LIMIT, |offset| {
+            if offset > start {
+                thread::sleep(RATE_LIMIT);
+            }
+            self.get_listing_page(&request::MangaList {
+                offset,
+                ..query.clone()
+            })
+        })?;
+        if let Ok(mut offsets) = self.page_offsets.lock() {
+            offsets.insert(&key, page + 1, next);
+        }
+
+        Ok(manga)
+    }
 }
 
 impl Extension for Mangadex {
@@ -238,6 +980,11 @@ impl Extension for Mangadex {
             }
         }
 
+        let credentials = preferences::credentials(&self.preferences);
+        if self.auth.credentials() != credentials.as_ref() {
+            self.auth = auth::Auth::new(credentials);
+        }
+
         Ok(())
     }
 
@@ -269,7 +1016,8 @@ impl Extension for Mangadex {
     }
 
     fn get_latest_manga(&self, page: i64) -> anyhow::Result<Vec<MangaInfo>> {
-        self.get_manga_list(page, request::MangaList::default())
+        let access_token = self.optional_access_token();
+        self.get_latest_updates(page, access_token.as_deref())
     }
 
     fn search_manga(
@@ -278,12 +1026,35 @@ impl Extension for Mangadex {
         query: Option<String>,
         filters: Option<Vec<Input>>,
     ) -> anyhow::Result<Vec<MangaInfo>> {
+        let list_id = filters.iter().flatten().find_map(|filter| match filter {
+            Input::Text {
+                state: Some(state), ..
+            } if filter::CUSTOM_LIST_FILTER.eq(filter) => custom_list_id(state),
+            _ => None,
+        });
+        if let Some(list_id) = list_id {
+            return self.get_custom_list(&list_id, page);
+        }
+
         let query_list = if let Some(filters) = filters {
-            filters.into()
+            let query: request::MangaList = filters.into();
+            let search = |name: &str| self.search_authors(name);
+            let authors = resolve_authors(query.authors.clone(), search)?;
+            let artists = resolve_authors(query.artists.clone(), search)?;
+            // nothing can match an author filter when none of its names exist
+            if (authors.is_empty() && !query.authors.is_empty())
+                || (artists.is_empty() && !query.artists.is_empty())
+            {
+                return Ok(vec![]);
+            }
+            request::MangaList {
+                authors,
+                artists,
+                ..query
+            }
         } else if let Some(query) = query {
             request::MangaList {
                 title: Some(query),
-                content_rating: vec![Rating::Safe, Rating::Suggestive, Rating::Erotica, Rating::Pornographic],
                 ..Default::default()
             }
         } else {
@@ -300,33 +1071,80 @@ impl Extension for Mangadex {
         );
 
         let res: Results = self.client.get(&url).call()?.into_json()?;
-        if let dto::Data::Single { data, .. } = res.data {
-            map_result_to_manga(data).ok_or_else(|| anyhow!("no such manga"))
+        if let dto::Data::Single { mut data, .. } = res.data {
+            if let Relationship::Manga {
+                id, relationships, ..
+            } = &mut data
+            {
+                // the included cover is still used when volume covers are unavailable
+                if let Ok(covers) = self.get_covers(id) {
+                    relationships.extend(covers);
+                }
+            }
+
+            let manga = map_result_to_manga(data, &self.preferred_language())
+                .ok_or_else(|| anyhow!("no such manga"))?;
+            self.with_statistics(vec![manga], None)
+                .pop()
+                .ok_or_else(|| anyhow!("no such manga"))
         } else {
             bail!("invalid data");
         }
     }
 
-    fn get_chapters(&self, path: String) -> anyhow::Result<Vec<ChapterInfo>> {        
-        let url = format!(
-            "{}{}/feed?limit=500&contentRating[]=safe&contentRating[]=suggestive&contentRating[]=erotica&contentRating[]=pornographic&translatedLanguage[]=en&includes[]=scanlation_group",
-            URL, path
-        );
+    fn get_chapters(&self, path: String) -> anyhow::Result<Vec<ChapterInfo>> {
+        let query = request::MangaFeed {
+            translated_language: self.chapter_languages(),
+            content_rating: self.content_ratings(),
+            includes: vec!["scanlation_group".to_string()],
+            ..Default::default()
+        };
+        let data = fetch_feed(&self.client, &format!("{}{}/feed", URL, path), query)?;
 
-        let res: Results = self.client.get(&url).call()?.into_json()?;
-        if let dto::Data::Multiple { data, .. } = res.data {
-            Ok(data.into_iter().filter_map(map_result_to_chapter).collect())
+        let data = if preferences::hide_external_chapters(&self.preferences) {
+            data.into_iter()
+                .filter(|chapter| chapter::external_url(chapter).is_none())
+                .collect()
         } else {
-            bail!("invalid data");
-        }
+            data
+        };
+
+        let chapters = self
+            .group_filter()
+            .apply(data)
+            .into_iter()
+            .filter_map(map_result_to_chapter)
+            .collect();
+
+        // numbers from the chapter attributes are kept when the aggregate is unavailable
+        let numbers = self
+            .get_aggregate(&path)
+            .map(|aggregate| chapter::chapter_numbers(&aggregate))
+            .unwrap_or_default();
+        Ok(order_chapters(chapters, &numbers))
     }
 
-    fn get_pages(&self, path: String) -> anyhow::Result<Vec<String>> {        
+    fn get_pages(&self, path: String) -> anyhow::Result<Vec<String>> {
         let chapter_id = path.replace("/chapter/", "");
-        let url = format!("{}/at-home/server/{}", URL, chapter_id);
+        let data_saver = preferences::data_saver(&self.preferences);
+        let force_port_443 = preferences::force_port_443(&self.preferences);
+
+        let at_home = match self.get_at_home(&chapter_id, force_port_443) {
+            Ok(at_home) if !at_home.chapter.data.is_empty() => at_home,
+            at_home => {
+                self.check_external(&chapter_id)?;
+                at_home?
+            }
+        };
+
+        let pages = map_result_to_pages(at_home, data_saver);
+        if preferences::report_at_home(&self.preferences) {
+            if let Some(page) = pages.first() {
+                self.report_page(page);
+            }
+        }
 
-        let res: ResultsAtHome = self.client.get(&url).call()?.into_json()?;
-        Ok(map_result_to_pages(res))
+        Ok(pages)
     }
 
     fn headers(&self) -> std::collections::HashMap<String, String> {
@@ -334,7 +1152,8 @@ impl Extension for Mangadex {
     }
 
     fn filter_list(&self) -> Vec<Input> {
-        filter::FILTER_LIST.clone()
+        let tags = filter::TAG_CACHE.get_or_fetch(|| self.get_tags());
+        filter::filter_list(tags.as_deref())
     }
 }
 
@@ -342,6 +1161,602 @@ impl Extension for Mangadex {
 mod test {
     use super::*;
 
+    fn fixture(name: &str) -> Results {
+        let json = match name {
+            "feed_0" => include_str!("../fixtures/feed_0.json"),
+            "feed_1" => include_str!("../fixtures/feed_1.json"),
+            "feed_2" => include_str!("../fixtures/feed_2.json"),
+            "manga" => include_str!("../fixtures/manga.json"),
+            "authors" => include_str!("../fixtures/authors.json"),
+            "follows_feed" => include_str!("../fixtures/follows_feed.json"),
+            "chapter_external" => include_str!("../fixtures/chapter_external.json"),
+            "custom_list" => include_str!("../fixtures/custom_list.json"),
+            "feed_groups" => include_str!("../fixtures/feed_groups.json"),
+            _ => panic!("no fixture {name}"),
+        };
+
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_map_result_to_pages() {
+        let res: ResultsAtHome =
+            serde_json::from_str(include_str!("../fixtures/at_home.json")).unwrap();
+
+        let pages = map_result_to_pages(res.clone(), false);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0], "https://cmdxd98sb0x3yprd.mangadex.network/data/3ec70a1feb5d0e8d3ae3c7dbb7a8d7a2/1-8a4ee2a4d1cd8fd1b7ad5c0dd6e6cf54b7a7d43b24ad65b3bc6f1f8bd5efa4e1.png");
+
+        let pages = map_result_to_pages(res, true);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1], "https://cmdxd98sb0x3yprd.mangadex.network/data-saver/3ec70a1feb5d0e8d3ae3c7dbb7a8d7a2/2-1c9d1e7be2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9.jpg");
+    }
+
+    #[test]
+    fn test_at_home_url_and_report() {
+        assert_eq!(
+            at_home_url("03d3e4b9", false),
+            "https://api.mangadex.org/at-home/server/03d3e4b9"
+        );
+        assert_eq!(
+            at_home_url("03d3e4b9", true),
+            "https://api.mangadex.org/at-home/server/03d3e4b9?forcePort443=true"
+        );
+
+        assert!(should_report(
+            "https://cmdxd98sb0x3yprd.mangadex.network/data/hash/1.png"
+        ));
+        assert!(!should_report(
+            "https://uploads.mangadex.org/data/hash/1.png"
+        ));
+    }
+
+    fn authors(name: &str) -> Result<Vec<Relationship>> {
+        let authors = match fixture("authors").data {
+            dto::Data::Multiple { data, .. } => data,
+            _ => unreachable!(),
+        };
+
+        // stand-in for the fuzzy name search of `/author?name=`
+        let name = name.to_lowercase();
+        Ok(authors
+            .into_iter()
+            .filter(|author| match author {
+                Relationship::Author {
+                    attributes: Some(attributes),
+                    ..
+                } => attributes.name.to_lowercase().contains(&name),
+                _ => false,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_resolve_authors() {
+        let ids = resolve_authors(vec!["oda eiichiro".to_string()], authors).unwrap();
+        assert_eq!(ids, vec!["a1b2c3d4-0000-4000-8000-000000000001"]);
+
+        let ids = resolve_authors(vec!["Oda T".to_string(), "Oda".to_string()], authors).unwrap();
+        assert_eq!(
+            ids,
+            vec![
+                "a1b2c3d4-0000-4000-8000-000000000002",
+                "a1b2c3d4-0000-4000-8000-000000000003"
+            ]
+        );
+
+        let ids = resolve_authors(vec!["Eiichiro".to_string()], authors).unwrap();
+        assert_eq!(ids, vec!["a1b2c3d4-0000-4000-8000-000000000001"]);
+
+        let id = "f5ba408b-0e7a-484d-8d49-4e9125ac96de".to_string();
+        let ids = resolve_authors(vec![id.clone()], |_| panic!("ids are not searched")).unwrap();
+        assert_eq!(ids, vec![id]);
+
+        assert!(resolve_authors(vec!["Togashi".to_string()], authors)
+            .unwrap()
+            .is_empty());
+        let ids =
+            resolve_authors(vec!["Togashi".to_string(), "Eiichiro".to_string()], authors).unwrap();
+        assert_eq!(ids, vec!["a1b2c3d4-0000-4000-8000-000000000001"]);
+    }
+
+    #[test]
+    fn test_feed_manga_ids() {
+        let chapters = match fixture("follows_feed").data {
+            dto::Data::Multiple { data, .. } => data,
+            _ => unreachable!(),
+        };
+
+        let mut seen = HashSet::new();
+        assert_eq!(
+            feed_manga_ids(&chapters, &mut seen),
+            vec![
+                "a96676e5-8ae2-425e-b549-7f15dd34a6d8",
+                "32d76d19-8a05-4db0-9fc2-e0b0648fe9d0",
+                "d8a959f7-648e-4c8d-8f23-f1f3f8e129f3"
+            ]
+        );
+        // manga of an earlier page are not listed again
+        assert!(feed_manga_ids(&chapters, &mut seen).is_empty());
+    }
+
+    #[test]
+    fn test_credentials_preferences() {
+        let mut mangadex = Mangadex::default();
+        assert!(mangadex.access_token().unwrap().is_none());
+
+        let credentials = [
+            ("Username", "reader"),
+            ("Password", "hunter2"),
+            ("API client id", "personal-client-reader"),
+        ];
+        let input = credentials
+            .iter()
+            .map(|(name, state)| Input::Text {
+                name: name.to_string(),
+                state: Some(state.to_string()),
+            })
+            .collect();
+        mangadex.set_preferences(input).unwrap();
+        assert!(mangadex.auth.credentials().is_none());
+
+        mangadex
+            .set_preferences(vec![Input::Text {
+                name: "API client secret".to_string(),
+                state: Some(" secret ".to_string()),
+            }])
+            .unwrap();
+        let credentials = mangadex.auth.credentials().unwrap();
+        assert_eq!(credentials.username, "reader");
+        assert_eq!(credentials.client_secret, "secret");
+    }
+
+    #[test]
+    fn test_content_ratings() {
+        let mut mangadex = Mangadex::default();
+        assert_eq!(
+            mangadex.content_ratings(),
+            vec![Rating::Safe, Rating::Suggestive, Rating::Erotica]
+        );
+
+        mangadex
+            .set_preferences(vec![Input::Group {
+                name: "Allowed content ratings".to_string(),
+                state: vec![Input::Checkbox {
+                    name: "safe".to_string(),
+                    state: Some(true),
+                }],
+            }])
+            .unwrap();
+        assert_eq!(mangadex.content_ratings(), vec![Rating::Safe]);
+
+        mangadex
+            .set_preferences(vec![Input::Group {
+                name: "Allowed content ratings".to_string(),
+                state: vec![],
+            }])
+            .unwrap();
+        assert_eq!(mangadex.content_ratings(), vec![Rating::Safe]);
+    }
+
+    #[test]
+    fn test_allowed_ratings() {
+        let allowed = [Rating::Safe, Rating::Suggestive];
+        assert_eq!(allowed_ratings(&[], &allowed).unwrap(), allowed);
+        assert_eq!(
+            allowed_ratings(&[Rating::Suggestive, Rating::Erotica], &allowed).unwrap(),
+            vec![Rating::Suggestive]
+        );
+        assert!(allowed_ratings(&[Rating::Pornographic], &allowed).is_none());
+    }
+
+    fn manga_fixture() -> Relationship {
+        match fixture("manga").data {
+            dto::Data::Single { data } => data,
+            _ => panic!("expected a single manga"),
+        }
+    }
+
+    #[test]
+    fn test_language_preferences() {
+        let mut mangadex = Mangadex::default();
+        assert_eq!(mangadex.chapter_languages(), vec!["en"]);
+        assert_eq!(mangadex.preferred_language(), "en");
+
+        let mut prefs = mangadex.get_preferences().unwrap();
+        for pref in prefs.iter_mut() {
+            match pref {
+                Input::Group { state, .. } => {
+                    for input in state.iter_mut() {
+                        if let Input::Checkbox { name, state } = input {
+                            *state = Some(name == "es-la" || name == "pt-br");
+                        }
+                    }
+                }
+                Input::Select { values, state, .. } => {
+                    *state = values
+                        .iter()
+                        .position(|v| matches!(v, InputType::String(lang) if lang == "es"))
+                        .map(|i| i as i64);
+                }
+                _ => {}
+            }
+        }
+        mangadex.set_preferences(prefs).unwrap();
+
+        assert_eq!(mangadex.chapter_languages(), vec!["es-la", "pt-br"]);
+        assert_eq!(mangadex.preferred_language(), "es");
+    }
+
+    #[test]
+    fn test_map_result_to_manga_language() {
+        let manga = map_result_to_manga(manga_fixture(), "en").unwrap();
+        assert_eq!(manga.title, "Komi-san wa Komyushou Desu.");
+        assert!(manga
+            .description
+            .unwrap()
+            .starts_with("Komi-san is a beautiful"));
+
+        let manga = map_result_to_manga(manga_fixture(), "es").unwrap();
+        assert_eq!(manga.title, "Komi-san no puede comunicarse");
+        assert!(manga
+            .description
+            .unwrap()
+            .starts_with("Komi-san es una chica hermosa y admirable.\n\n"));
+
+        let manga = map_result_to_manga(manga_fixture(), "ja").unwrap();
+        assert_eq!(manga.title, "古見さんは、コミュ症です。");
+
+        let manga = map_result_to_manga(manga_fixture(), "fr").unwrap();
+        assert_eq!(manga.title, "Komi-san wa Komyushou Desu.");
+    }
+
+    #[test]
+    fn test_map_result_to_manga_metadata() {
+        let manga = map_result_to_manga(manga_fixture(), "en").unwrap();
+        let description = manga.description.unwrap();
+        let metadata = description.split("\n\n").last().unwrap();
+        assert_eq!(
+            metadata,
+            "Alternative Titles: Komi Can't Communicate; 古見さんは、コミュ症です。; Komi-san no puede comunicarse\n\
+             Year: 2016\n\
+             Demographic: shounen\n\
+             Original Language: ja\n\
+             Content Rating: safe\n\
+             Links:\n\
+             AniList: https://anilist.co/manga/97852\n\
+             Amazon: https://www.amazon.co.jp/gp/product/B07CBD9ZTD\n\
+             Anime-Planet: https://www.anime-planet.com/manga/komi-cant-communicate\n\
+             Official English: https://www.viz.com/komi-can-t-communicate\n\
+             Kitsu: https://kitsu.io/manga/37675\n\
+             MyAnimeList: https://myanimelist.net/manga/99007\n\
+             MangaUpdates: https://www.mangaupdates.com/series.html?id=127281\n\
+             Raw: https://www.sunday-webry.com/series/794"
+        );
+        assert_eq!(
+            manga.cover_url,
+            "https://uploads.mangadex.org/covers/a96676e5-8ae2-425e-b549-7f15dd34a6d8/cbd0d7f4-6b4b-4ccd-bb87-3b4e2b3e6a76.jpg"
+        );
+    }
+
+    #[test]
+    fn test_map_result_to_chapter_external() {
+        let chapter = match fixture("chapter_external").data {
+            dto::Data::Single { data } => data,
+            _ => unreachable!(),
+        };
+
+        let chapter = map_result_to_chapter(chapter).unwrap();
+        assert_eq!(chapter.title, "[External] Ch. 201 - The Sea Cat");
+        assert_eq!(chapter.scanlator.as_deref(), Some("MangaPlus"));
+
+        let chapters = match fixture("feed_0").data {
+            dto::Data::Multiple { data, .. } => data,
+            _ => unreachable!(),
+        };
+        let chapter = map_result_to_chapter(chapters[0].clone()).unwrap();
+        assert!(!chapter.title.starts_with("[External]"));
+    }
+
+    #[test]
+    fn test_custom_list() {
+        let id = "8018a70b-1492-4f91-a584-7451d7787f7a";
+        assert_eq!(custom_list_id(id).as_deref(), Some(id));
+        assert_eq!(
+            custom_list_id(&format!(
+                " https://mangadex.org/list/{id}/team-picks?tab=titles "
+            ))
+            .as_deref(),
+            Some(id)
+        );
+        assert_eq!(custom_list_id("https://mangadex.org/list/team-picks"), None);
+
+        let list = match fixture("custom_list").data {
+            dto::Data::Single { data } => data,
+            _ => unreachable!(),
+        };
+        let page = custom_list_page(&list, 1);
+        assert_eq!(page.len(), 20);
+        assert_eq!(page[0], "00000001-0000-4000-8000-000000000001");
+        let page = custom_list_page(&list, 2);
+        assert_eq!(
+            page,
+            (21..=25)
+                .map(|i| format!("{:08}-0000-4000-8000-{:012}", i, i))
+                .collect::<Vec<_>>()
+        );
+        assert!(custom_list_page(&list, 3).is_empty());
+    }
+
+    #[test]
+    fn test_apply_statistics() {
+        let res: ResultsStatistics =
+            serde_json::from_str(include_str!("../fixtures/statistics.json")).unwrap();
+        let manga = |id: &str| MangaInfo {
+            path: format!("/manga/{}", id),
+            description: None,
+            ..map_result_to_manga(manga_fixture(), "en").unwrap()
+        };
+        let list = || {
+            vec![
+                map_result_to_manga(manga_fixture(), "en").unwrap(),
+                manga("32d76d19-8a05-4db0-9fc2-e0b0648fe9d0"),
+                manga("d8a959f7-648e-4c8d-8f23-f1f3f8e129f3"),
+                manga("00000000-0000-4000-8000-000000000000"),
+            ]
+        };
+
+        let all = apply_statistics(list(), &res.statistics, None);
+        assert_eq!(all.len(), 4);
+        assert!(all[0]
+            .description
+            .as_deref()
+            .unwrap()
+            .ends_with("\nRating: 8.61\nFollows: 158937"));
+        assert_eq!(
+            all[1].description.as_deref(),
+            Some("Rating: 6.46\nFollows: 2048")
+        );
+        assert_eq!(all[2].description.as_deref(), Some("Follows: 0"));
+        assert_eq!(all[3].description, None);
+
+        let rated = apply_statistics(list(), &res.statistics, Some(7.0));
+        assert_eq!(
+            rated.iter().map(manga_id).collect::<Vec<_>>(),
+            vec![
+                "a96676e5-8ae2-425e-b549-7f15dd34a6d8",
+                "00000000-0000-4000-8000-000000000000"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_page() {
+        let listing = |offset: i64, kept: usize| ListingPage {
+            items: (0..kept)
+                .map(|i| MangaInfo {
+                    path: format!("/manga/{}", offset + i as i64),
+                    ..map_result_to_manga(manga_fixture(), "en").unwrap()
+                })
+                .collect(),
+            listed: PAGE_LIMIT,
+            total: 1000,
+        };
+
+        // pages are read until enough manga are kept
+        let mut offsets = vec![];
+        let (manga, next) = fill_page(40, PAGE_LIMIT, |offset| {
+            offsets.push(offset);
+            Ok(listing(offset, 8))
+        })
+        .unwrap();
+        assert_eq!(offsets, vec![40, 60, 80]);
+        assert_eq!(manga.len(), 24);
+        assert_eq!(next, 100);
+
+        // at most `MAX_FILL_REQUESTS` pages are read
+        let (manga, next) = fill_page(0, PAGE_LIMIT, |offset| Ok(listing(offset, 0))).unwrap();
+        assert!(manga.is_empty());
+        assert_eq!(next, PAGE_LIMIT * MAX_FILL_REQUESTS as i64);
+
+        // the end of the listing ends the page
+        let (manga, next) = fill_page(0, PAGE_LIMIT, |offset| {
+            Ok(ListingPage {
+                total: 30,
+                ..listing(offset, 5)
+            })
+        })
+        .unwrap();
+        assert_eq!(manga.len(), 10);
+        assert_eq!(next, 40);
+    }
+
+    #[test]
+    fn test_page_offsets() {
+        let mut offsets = PageOffsets::default();
+        offsets.insert("title=a", 2, 45);
+        offsets.insert("title=a", 3, 80);
+        assert_eq!(offsets.get("title=a", 2), Some(45));
+        assert_eq!(offsets.get("title=a", 4), None);
+        assert_eq!(offsets.get("title=b", 2), None);
+
+        // only the offsets of the last query are kept
+        offsets.insert("title=b", 2, 20);
+        assert_eq!(offsets.get("title=a", 3), None);
+        assert_eq!(offsets.offsets.len(), 1);
+    }
+
+    #[test]
+    fn test_order_chapters() {
+        let chapters = match fixture("feed_groups").data {
+            dto::Data::Multiple { data, .. } => data,
+            _ => unreachable!(),
+        };
+        let chapters: Vec<ChapterInfo> = chapters
+            .into_iter()
+            .filter_map(map_result_to_chapter)
+            .collect();
+        assert_eq!(chapters[0].title, "Vol. 1 Ch. 1");
+
+        let numbers = chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| (chapter.path[9..].to_string(), i as f64 / 10.0))
+            .collect();
+        let ordered = order_chapters(chapters, &numbers);
+        assert!(ordered
+            .windows(2)
+            .all(|pair| pair[0].number > pair[1].number));
+        assert_eq!(ordered.last().unwrap().number, 0.0);
+    }
+
+    #[test]
+    fn test_fetch_all_walks_every_page() {
+        let mut offsets = vec![];
+        let data = fetch_all(2, |offset| {
+            offsets.push(offset);
+            Ok(fixture(&format!("feed_{}", offset / 2)))
+        })
+        .unwrap();
+
+        assert_eq!(offsets, vec![0, 2, 4]);
+        let chapters: Vec<ChapterInfo> =
+            data.into_iter().filter_map(map_result_to_chapter).collect();
+        let numbers: Vec<f64> = chapters.iter().map(|ch| ch.number).collect();
+        assert_eq!(numbers, vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_fetch_all_stops_on_empty_page() {
+        let mut calls = 0;
+        let data = fetch_all(2, |offset| {
+            calls += 1;
+            let mut res = fixture("feed_0");
+            if let dto::Data::Multiple { data, .. } = &mut res.data {
+                if offset > 0 {
+                    data.clear();
+                }
+            }
+            Ok(res)
+        })
+        .unwrap();
+
+        assert_eq!(calls, 2);
+        assert_eq!(data.len(), 2);
+    }
+
+    #[test]
+    fn test_fetch_all_stops_at_max_offset() {
+        let mut last_offset = 0;
+        fetch_all(FEED_LIMIT, |offset| {
+            last_offset = offset;
+            let mut res = fixture("feed_0");
+            if let dto::Data::Multiple { total, .. } = &mut res.data {
+                *total = 100_000;
+            }
+            Ok(res)
+        })
+        .unwrap();
+
+        assert_eq!(last_offset + FEED_LIMIT, MAX_OFFSET);
+    }
+
+    /// Answer `requests` requests on a local port with `respond`, the request
+    /// lines received are returned when the server thread is joined
+    fn stub_server<F>(requests: usize, respond: F) -> (String, thread::JoinHandle<Vec<String>>)
+    where
+        F: Fn(&str) -> String + Send + 'static,
+    {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut lines = vec![];
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let body = respond(line.trim());
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                lines.push(line.trim().to_string());
+            }
+            lines
+        });
+
+        (url, server)
+    }
+
+    #[test]
+    fn test_fetch_feed_stub_server() {
+        let pages = (MAX_OFFSET / FEED_LIMIT) as usize;
+        let (url, server) = stub_server(pages, |_| {
+            let mut page: serde_json::Value =
+                serde_json::from_str(include_str!("../fixtures/feed_0.json")).unwrap();
+            page["total"] = 100_000.into();
+            page.to_string()
+        });
+
+        let query = request::MangaFeed {
+            translated_language: vec!["en".to_string()],
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let data = fetch_feed(
+            &build_ureq_agent(None, None),
+            &format!("{}/manga/x/feed", url),
+            query,
+        )
+        .unwrap();
+        assert!(start.elapsed() >= RATE_LIMIT * (pages as u32 - 1));
+        assert_eq!(data.len(), 2);
+
+        let lines = server.join().unwrap();
+        assert!(lines
+            .iter()
+            .all(|line| line.starts_with("GET /manga/x/feed?limit=500&offset=")));
+        let offsets: Vec<i64> = lines
+            .iter()
+            .map(|line| {
+                let offset = line.split("offset=").nth(1).unwrap();
+                offset.split('&').next().unwrap().parse().unwrap()
+            })
+            .collect();
+        let expected: Vec<i64> = (0..MAX_OFFSET).step_by(FEED_LIMIT as usize).collect();
+        assert_eq!(offsets, expected);
+    }
+
+    #[test]
+    fn test_probe_page_stub_server() {
+        let (url, server) = stub_server(1, |_| "image".to_string());
+        let mangadex = Mangadex::default();
+        let report = mangadex.probe_page(&format!("{}/data/hash/1.png", url));
+        assert!(report.success);
+        assert!(!report.cached);
+        assert_eq!(report.bytes, 5);
+        assert_eq!(
+            server.join().unwrap(),
+            vec!["HEAD /data/hash/1.png HTTP/1.1"]
+        );
+
+        // nothing listens on the port once the server is done
+        let report = mangadex.probe_page(&format!("{}/data/hash/1.png", url));
+        assert!(!report.success);
+        assert_eq!(report.bytes, 0);
+    }
+
     #[test]
     fn test_get_latest_manga() {
         let mangadex = Mangadex::default();
@@ -0,0 +1,177 @@
+use crate::dto::manga::{CoverAttributes, MangaAttributes};
+use crate::dto::MangaStatistics;
+
+/// Web page of an entry of `MangaAttributes.links`
+pub fn link_url(key: &str, value: &str) -> Option<(&'static str, String)> {
+    let is_number = value.chars().all(|c| c.is_ascii_digit());
+    let link = match key {
+        "al" => ("AniList", format!("https://anilist.co/manga/{}", value)),
+        "ap" => (
+            "Anime-Planet",
+            format!("https://www.anime-planet.com/manga/{}", value),
+        ),
+        "bw" => ("BookWalker", format!("https://bookwalker.jp/{}", value)),
+        "kt" if is_number => ("Kitsu", format!("https://kitsu.io/manga/{}", value)),
+        "kt" => ("Kitsu", format!("https://kitsu.io/manga?slug={}", value)),
+        "mal" => (
+            "MyAnimeList",
+            format!("https://myanimelist.net/manga/{}", value),
+        ),
+        "mu" if is_number => (
+            "MangaUpdates",
+            format!("https://www.mangaupdates.com/series.html?id={}", value),
+        ),
+        "mu" => (
+            "MangaUpdates",
+            format!("https://www.mangaupdates.com/series/{}", value),
+        ),
+        "nu" => (
+            "NovelUpdates",
+            format!("https://www.novelupdates.com/series/{}", value),
+        ),
+        "amz" => ("Amazon", value.to_string()),
+        "cdj" => ("CDJapan", value.to_string()),
+        "ebj" => ("eBookJapan", value.to_string()),
+        "raw" => ("Raw", value.to_string()),
+        "engtl" => ("Official English", value.to_string()),
+        _ => return None,
+    };
+
+    Some(link)
+}
+
+/// Metadata lines appended to the description, `title` is left out of the
+/// alternative titles
+pub fn describe(attr: &MangaAttributes, title: &str) -> Vec<String> {
+    let mut lines = vec![];
+
+    let mut alt_titles: Vec<&str> = vec![];
+    for alt_title in attr.alt_titles.iter().flat_map(|titles| titles.values()) {
+        if alt_title != title && !alt_titles.contains(&alt_title.as_str()) {
+            alt_titles.push(alt_title);
+        }
+    }
+    if !alt_titles.is_empty() {
+        lines.push(format!("Alternative Titles: {}", alt_titles.join("; ")));
+    }
+
+    if let Some(year) = attr.year {
+        lines.push(format!("Year: {}", year));
+    }
+    if let Some(demographic) = &attr.publication_demographic {
+        lines.push(format!("Demographic: {}", demographic));
+    }
+    lines.push(format!("Original Language: {}", attr.original_language));
+
+    match (
+        attr.last_volume.as_deref().filter(|v| !v.is_empty()),
+        attr.last_chapter.as_deref().filter(|c| !c.is_empty()),
+    ) {
+        (Some(volume), Some(chapter)) => {
+            lines.push(format!("Last Chapter: Vol. {} Ch. {}", volume, chapter))
+        }
+        (None, Some(chapter)) => lines.push(format!("Last Chapter: Ch. {}", chapter)),
+        _ => {}
+    }
+
+    lines.push(format!("Content Rating: {}", attr.content_rating));
+
+    let mut links: Vec<(&String, &String)> = attr.links.iter().collect();
+    links.sort_unstable();
+    let links: Vec<String> = links
+        .into_iter()
+        .filter_map(|(key, value)| link_url(key, value))
+        .map(|(name, url)| format!("{}: {}", name, url))
+        .collect();
+    if !links.is_empty() {
+        lines.push(format!("Links:\n{}", links.join("\n")));
+    }
+
+    lines
+}
+
+pub fn describe_statistics(statistics: &MangaStatistics) -> Vec<String> {
+    let mut lines = vec![];
+    if let Some(rating) = statistics.rating() {
+        lines.push(format!("Rating: {:.2}", rating));
+    }
+    if let Some(follows) = statistics.follows {
+        lines.push(format!("Follows: {}", follows));
+    }
+
+    lines
+}
+
+fn volume_number(cover: &CoverAttributes) -> f64 {
+    cover
+        .volume
+        .as_deref()
+        .and_then(|volume| volume.parse().ok())
+        .unwrap_or(-1.0)
+}
+
+/// Cover of the latest volume, covers in the original language win over
+/// localized editions
+pub fn pick_cover<'a>(
+    covers: &'a [CoverAttributes],
+    original_language: &str,
+) -> Option<&'a CoverAttributes> {
+    let original = |cover: &CoverAttributes| cover.locale.as_deref() == Some(original_language);
+    covers.iter().max_by(|a, b| {
+        original(a)
+            .cmp(&original(b))
+            .then(volume_number(a).total_cmp(&volume_number(b)))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cover(volume: Option<&str>, locale: &str, file_name: &str) -> CoverAttributes {
+        serde_json::from_value(serde_json::json!({
+            "volume": volume,
+            "fileName": file_name,
+            "description": "",
+            "locale": locale,
+            "version": 1,
+            "createdAt": "2022-06-18T12:00:00+00:00",
+            "updatedAt": "2022-06-18T12:00:00+00:00",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pick_cover() {
+        let covers = vec![
+            cover(Some("1"), "ja", "1.jpg"),
+            cover(Some("10"), "ja", "10.jpg"),
+            cover(Some("2"), "ja", "2.jpg"),
+            cover(Some("12"), "en", "12-en.jpg"),
+            cover(None, "ja", "none.jpg"),
+        ];
+
+        let picked = pick_cover(&covers, "ja").unwrap();
+        assert_eq!(picked.file_name, "10.jpg");
+        let picked = pick_cover(&covers, "ko").unwrap();
+        assert_eq!(picked.file_name, "12-en.jpg");
+        assert!(pick_cover(&[], "ja").is_none());
+    }
+
+    #[test]
+    fn test_link_url() {
+        assert_eq!(
+            link_url("mu", "127281").unwrap().1,
+            "https://www.mangaupdates.com/series.html?id=127281"
+        );
+        assert_eq!(
+            link_url("mu", "1x2ykw3").unwrap().1,
+            "https://www.mangaupdates.com/series/1x2ykw3"
+        );
+        assert_eq!(
+            link_url("al", "97852").unwrap(),
+            ("AniList", "https://anilist.co/manga/97852".to_string())
+        );
+        assert!(link_url("unknown", "1").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use lazy_static::lazy_static;
+use tanoshi_lib::prelude::{Input, InputType};
+
+use crate::auth::Credentials;
+use crate::dto::manga::Rating;
+
+/// Language codes MangaDex uses for titles, descriptions and translations
+pub static LANGUAGES: &[&str] = &[
+    "en", "ja", "ja-ro", "ko", "ko-ro", "zh", "zh-hk", "zh-ro", "es", "es-la", "pt", "pt-br", "fr",
+    "de", "it", "ru", "uk", "pl", "tr", "ar", "id", "vi", "th", "ms", "fil", "nl", "sv", "cs",
+    "hu", "ro", "el", "he", "fa", "hi", "bn", "mn", "my", "ne", "ca", "da", "fi", "no", "bg", "hr",
+    "lt", "sr", "ta", "kk", "la", "eo",
+];
+
+lazy_static! {
+    pub static ref CHAPTER_LANGUAGES: Input = Input::Group {
+        name: "Chapter languages".to_string(),
+        state: LANGUAGES
+            .iter()
+            .map(|lang| Input::Checkbox {
+                name: lang.to_string(),
+                state: Some(*lang == "en"),
+            })
+            .collect(),
+    };
+    pub static ref PREFERRED_LANGUAGE: Input = Input::Select {
+        name: "Preferred title/description language".to_string(),
+        values: LANGUAGES
+            .iter()
+            .map(|lang| InputType::String(lang.to_string()))
+            .collect(),
+        state: Some(0),
+    };
+    pub static ref CONTENT_RATINGS: Input = Input::Group {
+        name: "Allowed content ratings".to_string(),
+        state: ["safe", "suggestive", "erotica", "pornographic"]
+            .iter()
+            .map(|rating| Input::Checkbox {
+                name: rating.to_string(),
+                state: Some(*rating != "pornographic"),
+            })
+            .collect(),
+    };
+    pub static ref PREFERRED_GROUPS: Input = Input::Text {
+        name: "Preferred groups".to_string(),
+        state: None,
+    };
+    pub static ref BLOCKED_GROUPS: Input = Input::Text {
+        name: "Blocked groups".to_string(),
+        state: None,
+    };
+    pub static ref ONE_CHAPTER_PER_NUMBER: Input = Input::Checkbox {
+        name: "One chapter per number".to_string(),
+        state: Some(false),
+    };
+    pub static ref HIDE_EXTERNAL_CHAPTERS: Input = Input::Checkbox {
+        name: "Hide external chapters".to_string(),
+        state: Some(false),
+    };
+    pub static ref DATA_SAVER: Input = Input::Checkbox {
+        name: "Data saver".to_string(),
+        state: Some(false),
+    };
+    pub static ref FORCE_PORT_443: Input = Input::Checkbox {
+        name: "Force port 443".to_string(),
+        state: Some(false),
+    };
+    pub static ref REPORT_AT_HOME: Input = Input::Checkbox {
+        name: "Report image loads to MangaDex@Home".to_string(),
+        state: Some(false),
+    };
+    pub static ref USERNAME: Input = Input::Text {
+        name: "Username".to_string(),
+        state: None,
+    };
+    pub static ref PASSWORD: Input = Input::Text {
+        name: "Password".to_string(),
+        state: None,
+    };
+    pub static ref CLIENT_ID: Input = Input::Text {
+        name: "API client id".to_string(),
+        state: None,
+    };
+    pub static ref CLIENT_SECRET: Input = Input::Text {
+        name: "API client secret".to_string(),
+        state: None,
+    };
+    pub static ref PREFERENCES: Vec<Input> = vec![
+        CHAPTER_LANGUAGES.clone(),
+        PREFERRED_LANGUAGE.clone(),
+        CONTENT_RATINGS.clone(),
+        PREFERRED_GROUPS.clone(),
+        BLOCKED_GROUPS.clone(),
+        ONE_CHAPTER_PER_NUMBER.clone(),
+        HIDE_EXTERNAL_CHAPTERS.clone(),
+        DATA_SAVER.clone(),
+        FORCE_PORT_443.clone(),
+        REPORT_AT_HOME.clone(),
+        USERNAME.clone(),
+        PASSWORD.clone(),
+        CLIENT_ID.clone(),
+        CLIENT_SECRET.clone(),
+    ];
+}
+
+fn find<'a>(preferences: &'a [Input], input: &Input) -> Option<&'a Input> {
+    preferences.iter().find(|pref| input.eq(pref))
+}
+
+/// Names of the checked boxes of a group preference
+fn checked_names(preferences: &[Input], input: &Input) -> Vec<String> {
+    if let Some(Input::Group { state, .. }) = find(preferences, input) {
+        state
+            .iter()
+            .filter_map(|input| match input {
+                Input::Checkbox {
+                    name,
+                    state: Some(true),
+                } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+/// Checked languages of `CHAPTER_LANGUAGES`, empty means every language
+pub fn chapter_languages(preferences: &[Input]) -> Vec<String> {
+    checked_names(preferences, &CHAPTER_LANGUAGES)
+}
+
+/// Ratings every listing and feed is restricted to, only safe when nothing is
+/// checked as MangaDex would otherwise apply its own default
+pub fn content_ratings(preferences: &[Input]) -> Vec<Rating> {
+    let ratings: Vec<Rating> = checked_names(preferences, &CONTENT_RATINGS)
+        .iter()
+        .filter_map(|name| name.parse().ok())
+        .collect();
+    if ratings.is_empty() {
+        vec![Rating::Safe]
+    } else {
+        ratings
+    }
+}
+
+pub fn preferred_language(preferences: &[Input]) -> String {
+    if let Some(Input::Select { values, state, .. }) = find(preferences, &PREFERRED_LANGUAGE) {
+        if let Some(InputType::String(lang)) = state.and_then(|i| values.get(i as usize)) {
+            return lang.clone();
+        }
+    }
+
+    "en".to_string()
+}
+
+fn text(preferences: &[Input], input: &Input) -> Option<String> {
+    match find(preferences, input) {
+        Some(Input::Text {
+            state: Some(state), ..
+        }) if !state.trim().is_empty() => Some(state.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Comma separated entries of a text preference
+fn text_list(preferences: &[Input], input: &Input) -> Vec<String> {
+    if let Some(Input::Text {
+        state: Some(state), ..
+    }) = find(preferences, input)
+    {
+        state
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+fn checked(preferences: &[Input], input: &Input) -> bool {
+    matches!(
+        find(preferences, input),
+        Some(Input::Checkbox {
+            state: Some(true),
+            ..
+        })
+    )
+}
+
+/// Scanlation group names or ids, in order of preference
+pub fn preferred_groups(preferences: &[Input]) -> Vec<String> {
+    text_list(preferences, &PREFERRED_GROUPS)
+}
+
+/// Scanlation group names or ids
+pub fn blocked_groups(preferences: &[Input]) -> Vec<String> {
+    text_list(preferences, &BLOCKED_GROUPS)
+}
+
+pub fn one_chapter_per_number(preferences: &[Input]) -> bool {
+    checked(preferences, &ONE_CHAPTER_PER_NUMBER)
+}
+
+/// Drop chapters hosted on other sites instead of flagging them
+pub fn hide_external_chapters(preferences: &[Input]) -> bool {
+    checked(preferences, &HIDE_EXTERNAL_CHAPTERS)
+}
+
+pub fn data_saver(preferences: &[Input]) -> bool {
+    checked(preferences, &DATA_SAVER)
+}
+
+/// Ask for MangaDex@Home nodes served on port 443, for networks blocking other ports
+pub fn force_port_443(preferences: &[Input]) -> bool {
+    checked(preferences, &FORCE_PORT_443)
+}
+
+pub fn report_at_home(preferences: &[Input]) -> bool {
+    checked(preferences, &REPORT_AT_HOME)
+}
+
+/// Login of a MangaDex personal API client, only when every field is set
+pub fn credentials(preferences: &[Input]) -> Option<Credentials> {
+    Some(Credentials {
+        username: text(preferences, &USERNAME)?,
+        password: text(preferences, &PASSWORD)?,
+        client_id: text(preferences, &CLIENT_ID)?,
+        client_secret: text(preferences, &CLIENT_SECRET)?,
+    })
+}
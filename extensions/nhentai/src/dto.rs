@@ -0,0 +1,276 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_aux::prelude::deserialize_number_from_string;
+
+pub static IMAGE_URL: &str = "https://i.nhentai.net";
+pub static THUMBNAIL_URL: &str = "https://t.nhentai.net";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SearchResult {
+    pub result: Vec<Gallery>,
+    pub num_pages: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Gallery {
+    /// A number in gallery responses and sometimes a string in search results
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub id: i64,
+    pub media_id: String,
+    pub title: Title,
+    pub images: Images,
+    #[serde(default)]
+    pub scanlator: String,
+    /// Unix timestamp
+    pub upload_date: i64,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    pub num_pages: i64,
+    #[serde(default)]
+    pub num_favorites: i64,
+}
+
+impl Gallery {
+    pub fn path(&self) -> String {
+        format!("/g/{}", self.id)
+    }
+
+    pub fn cover_url(&self) -> String {
+        format!(
+            "{}/galleries/{}/cover.{}",
+            THUMBNAIL_URL,
+            self.media_id,
+            self.images.cover.t.extension()
+        )
+    }
+
+    /// Full size images in reading order
+    pub fn page_urls(&self) -> Vec<String> {
+        self.images
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| {
+                format!(
+                    "{}/galleries/{}/{}.{}",
+                    IMAGE_URL,
+                    self.media_id,
+                    i + 1,
+                    page.t.extension()
+                )
+            })
+            .collect()
+    }
+
+    /// Names of the tags of a type, in response order
+    pub fn tags_of(&self, tag_type: TagType) -> Vec<String> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.tag_type == tag_type)
+            .map(|tag| tag.name.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Title {
+    pub english: Option<String>,
+    pub japanese: Option<String>,
+    pub pretty: Option<String>,
+}
+
+/// Which title of a gallery is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleKind {
+    Pretty,
+    English,
+    Japanese,
+}
+
+impl Title {
+    /// The title of `kind`, or the first other title when it is missing
+    pub fn get(&self, kind: TitleKind) -> Option<&str> {
+        let preferred = match kind {
+            TitleKind::Pretty => &self.pretty,
+            TitleKind::English => &self.english,
+            TitleKind::Japanese => &self.japanese,
+        };
+        [preferred, &self.pretty, &self.english, &self.japanese]
+            .into_iter()
+            .flatten()
+            .map(|title| title.trim())
+            .find(|title| !title.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Images {
+    pub pages: Vec<Image>,
+    pub cover: Image,
+    pub thumbnail: Image,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Image {
+    pub t: ImageType,
+    pub w: Option<i64>,
+    pub h: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ImageType {
+    #[serde(rename = "j")]
+    Jpg,
+    #[serde(rename = "p")]
+    Png,
+    #[serde(rename = "g")]
+    Gif,
+    #[serde(rename = "w")]
+    Webp,
+}
+
+impl ImageType {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageType::Jpg => "jpg",
+            ImageType::Png => "png",
+            ImageType::Gif => "gif",
+            ImageType::Webp => "webp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Tag {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub tag_type: TagType,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagType {
+    Tag,
+    Parody,
+    Character,
+    Artist,
+    Group,
+    Language,
+    Category,
+}
+
+impl Display for TagType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagType::Tag => write!(f, "tag"),
+            TagType::Parody => write!(f, "parody"),
+            TagType::Character => write!(f, "character"),
+            TagType::Artist => write!(f, "artist"),
+            TagType::Group => write!(f, "group"),
+            TagType::Language => write!(f, "language"),
+            TagType::Category => write!(f, "category"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AutocompleteResult {
+    pub result: Vec<Tag>,
+}
+
+pub mod request {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum Sort {
+        Popular,
+        PopularWeek,
+        PopularToday,
+        Date,
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    pub struct Search {
+        pub query: String,
+        pub page: i64,
+        pub sort: Option<Sort>,
+    }
+
+    impl Search {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
+
+    /// Query of the favorites page, `q` searches within the favorites
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    pub struct Favorites {
+        pub q: Option<String>,
+        pub page: i64,
+    }
+
+    impl Favorites {
+        pub fn to_query_string(&self) -> anyhow::Result<String> {
+            Ok(serde_qs::to_string(self)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::request::{Search, Sort};
+    use super::*;
+
+    #[test]
+    fn test_gallery() {
+        let gallery: Gallery =
+            serde_json::from_str(include_str!("../fixtures/gallery.json")).unwrap();
+
+        assert_eq!(gallery.path(), "/g/385965");
+        assert_eq!(
+            gallery.cover_url(),
+            "https://t.nhentai.net/galleries/2099700/cover.jpg"
+        );
+
+        let pages = gallery.page_urls();
+        assert_eq!(pages.len() as i64, gallery.num_pages);
+        assert_eq!(pages[0], "https://i.nhentai.net/galleries/2099700/1.jpg");
+        assert_eq!(pages[1], "https://i.nhentai.net/galleries/2099700/2.png");
+        assert_eq!(pages[2], "https://i.nhentai.net/galleries/2099700/3.webp");
+        assert_eq!(gallery.tags_of(TagType::Artist), vec!["hinasaki yo"]);
+    }
+
+    #[test]
+    fn test_search_result() {
+        let res: SearchResult =
+            serde_json::from_str(include_str!("../fixtures/search.json")).unwrap();
+
+        let ids: Vec<i64> = res.result.iter().map(|gallery| gallery.id).collect();
+        assert_eq!(ids, vec![385965, 177013]);
+        assert_eq!(res.result[1].images.cover.t, ImageType::Gif);
+        assert_eq!(
+            res.result[1].title.get(TitleKind::Japanese),
+            Some("METAMORPHOSIS")
+        );
+    }
+
+    #[test]
+    fn test_search_query_string() {
+        let search = Search {
+            query: "language:english -tag:netorare".to_string(),
+            page: 2,
+            sort: Some(Sort::PopularWeek),
+        };
+        assert_eq!(
+            search.to_query_string().unwrap(),
+            "query=language%3Aenglish+-tag%3Anetorare&page=2&sort=popular-week"
+        );
+    }
+}
@@ -1,10 +1,19 @@
-use anyhow::{anyhow, Result};
-use chrono::NaiveDateTime;
-use fancy_regex::Regex;
+mod dto;
+mod tags;
+
+use anyhow::{anyhow, bail, Result};
+use dto::request::{self, Sort};
+use dto::{AutocompleteResult, Gallery, SearchResult, Tag, TagType, TitleKind};
 use lazy_static::lazy_static;
-use networking::{build_flaresolverr_client, build_ureq_agent, Agent};
+use log::error;
+use networking::{
+    build_flaresolverr_agent, build_ureq_agent, cookie_value, get_flaresolverr_solution,
+    insert_cookie, Agent, CookieStore, FlareSolverrSolution,
+};
 use scraper::{Html, Selector};
 use std::env;
+use std::time::Duration;
+use tags::TagIndex;
 use tanoshi_lib::prelude::{
     ChapterInfo, Extension, Input, InputType, Lang, MangaInfo, PluginRegistrar,
 };
@@ -13,6 +22,9 @@ pub static ID: i64 = 6;
 pub static NAME: &str = "nhentai";
 pub static URL: &str = "https://nhentai.net";
 
+/// How long tag lookups are cached
+const TAG_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
 tanoshi_lib::export_plugin!(register);
 
 fn register(registrar: &mut dyn PluginRegistrar) {
@@ -54,7 +66,12 @@ lazy_static! {
         ],
         state: None
     };
+    static ref FAVORITES_FILTER: Input = Input::Checkbox {
+        name: "Favorites".to_string(),
+        state: Some(false)
+    };
     static ref FILTER_LIST: Vec<Input> = vec![
+        FAVORITES_FILTER.clone(),
         TAG_FILTER.clone(),
         CHARACTERS_FILTER.clone(),
         CATEGORIES_FILTER.clone(),
@@ -77,34 +94,172 @@ lazy_static! {
         name: "Blacklist Tag".to_string(),
         state: None
     };
-    static ref PREFERENCES: Vec<Input> = vec![LANGUAGE_SELECT.clone(), BLACKLIST_TAG.clone()];
+    static ref TITLE_SELECT: Input = Input::Select {
+        name: "Title".to_string(),
+        values: vec![
+            InputType::String("Pretty".to_string()),
+            InputType::String("English".to_string()),
+            InputType::String("Japanese".to_string()),
+        ],
+        state: Some(0)
+    };
+    static ref SESSION_COOKIE: Input = Input::Text {
+        name: "Session cookie (sessionid)".to_string(),
+        state: None
+    };
+    static ref USERNAME: Input = Input::Text {
+        name: "Username or email".to_string(),
+        state: None
+    };
+    static ref PASSWORD: Input = Input::Text {
+        name: "Password".to_string(),
+        state: None
+    };
+    static ref PREFERENCES: Vec<Input> = vec![
+        LANGUAGE_SELECT.clone(),
+        BLACKLIST_TAG.clone(),
+        TITLE_SELECT.clone(),
+        SESSION_COOKIE.clone(),
+        USERNAME.clone(),
+        PASSWORD.clone()
+    ];
 }
 
 pub struct NHentai {
     preferences: Vec<Input>,
     client: Agent,
+    /// Cloudflare clearance, kept to rebuild the client when the session changes
+    solution: Option<FlareSolverrSolution>,
+    tags: TagIndex,
 }
 
 impl Default for NHentai {
     fn default() -> Self {
+        // If flaresolverr_url is set, build the client with it
+        let solution = env::var("FLARESOLVERR_URL")
+            .ok()
+            .map(|flaresolverr_url| get_flaresolverr_solution(URL, &flaresolverr_url).unwrap());
+
         let mut instance = Self {
             preferences: PREFERENCES.clone(),
             client: build_ureq_agent(None, None),
+            solution,
+            tags: TagIndex::new(TAG_TTL),
         };
-
-        // If flaresolverr_url is set, build the client with it
-        if let Ok(flaresolverr_url) = env::var("FLARESOLVERR_URL") {
-            instance.client = build_flaresolverr_client(URL, &flaresolverr_url).unwrap();
-        }
+        instance.client = instance.build_client(CookieStore::default());
 
         instance
     }
 }
 
+/// Tag type searched by a text filter
+fn filter_tag_type(filter: &Input) -> Option<TagType> {
+    [
+        (&*TAG_FILTER, TagType::Tag),
+        (&*CHARACTERS_FILTER, TagType::Character),
+        (&*CATEGORIES_FILTER, TagType::Category),
+        (&*PARODIES_FILTER, TagType::Parody),
+        (&*ARTISTS_FILTER, TagType::Artist),
+        (&*GROUPS_FILTER, TagType::Group),
+    ]
+    .into_iter()
+    .find(|(input, _)| input.eq(&filter))
+    .map(|(_, tag_type)| tag_type)
+}
+
+/// Trimmed state of a text preference, `None` when empty
+fn text(preferences: &[Input], input: &Input) -> Option<String> {
+    match preferences.iter().find(|pref| input.eq(pref)) {
+        Some(Input::Text {
+            state: Some(state), ..
+        }) if !state.trim().is_empty() => Some(state.trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Session cookie and login of the account, a change logs out
+fn account(preferences: &[Input]) -> [Option<String>; 3] {
+    [
+        text(preferences, &SESSION_COOKIE),
+        text(preferences, &USERNAME),
+        text(preferences, &PASSWORD),
+    ]
+}
+
+/// Hidden `csrfmiddlewaretoken` field of a form
+fn csrf_token(html: &str) -> Option<String> {
+    let field = html.find("name=\"csrfmiddlewaretoken\"")?;
+    let tag_start = html[..field].rfind('<')?;
+    let tag_end = field + html[field..].find('>')?;
+    let tag = &html[tag_start..tag_end];
+    let value = tag.find("value=\"")? + "value=\"".len();
+    let len = tag[value..].find('"')?;
+
+    Some(tag[value..value + len].to_string())
+}
+
+/// Galleries of a favorites page, in page order. The page only has the
+/// caption title and a thumbnail, the details come with `get_manga_detail`
+fn parse_favorites(html: &str) -> Result<Vec<MangaInfo>> {
+    let selector = |selector: &str| {
+        Selector::parse(selector).map_err(|e| anyhow!("failed to parse selector: {:?}", e))
+    };
+    let favorite = selector(".gallery-favorite")?;
+    let caption = selector(".caption")?;
+    let img = selector("img")?;
+
+    let doc = Html::parse_document(html);
+    Ok(doc
+        .select(&favorite)
+        .filter_map(|el| {
+            let id: i64 = el.value().attr("data-id")?.parse().ok()?;
+            let title = el.select(&caption).next()?.text().collect::<String>();
+            let cover_url = el
+                .select(&img)
+                .next()
+                .and_then(|img| img.value().attr("data-src").or(img.value().attr("src")))
+                .unwrap_or_default();
+
+            Some(MangaInfo {
+                source_id: ID,
+                title: title.trim().to_string(),
+                author: vec![],
+                genre: vec![],
+                status: None,
+                description: None,
+                path: format!("/g/{id}"),
+                cover_url: cover_url.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Normalised comma separated tags and whether each is excluded, a `-`
+/// prefix or `exclude` excludes a tag
+fn split_tags(values: &str, exclude: bool) -> Vec<(bool, String)> {
+    values
+        .split(',')
+        .filter_map(|value| {
+            let value = value.trim();
+            let (excluded, value) = match value.strip_prefix('-') {
+                Some(value) => (true, value),
+                None => (exclude, value),
+            };
+            let value = tags::normalize(value);
+            (!value.is_empty()).then_some((excluded, value))
+        })
+        .collect()
+}
+
 impl NHentai {
-    fn query(&self, filters: Option<Vec<Input>>) -> String {
+    fn search_request(
+        &self,
+        page: i64,
+        filters: Option<Vec<Input>>,
+        sort: Option<Sort>,
+    ) -> Result<request::Search> {
         let mut query = vec![];
-        let mut sort = None;
+        let mut sort = sort;
         for pref in self.preferences.iter() {
             if LANGUAGE_SELECT.eq(pref) {
                 if let Input::Select { state, values, .. } = pref {
@@ -121,9 +276,7 @@ impl NHentai {
                     state: Some(state), ..
                 } = pref
                 {
-                    for tag in state.split(',') {
-                        query.push(format!("-tag:{}", tag.trim()))
-                    }
+                    query.extend(self.blacklist_terms(state));
                 }
             }
         }
@@ -132,112 +285,257 @@ impl NHentai {
             for filter in filters.iter() {
                 match filter {
                     Input::Text {
-                        name,
-                        state: Some(state),
-                        ..
-                    } if name == &TAG_FILTER.name() => {
-                        for tag in state.split(',') {
-                            if tag.starts_with('-') {
-                                query.push(format!(
-                                    "-{}:{}",
-                                    name.to_lowercase(),
-                                    tag.trim().replace("-", "")
-                                ))
-                            } else {
-                                query.push(format!("{}:{}", name.to_lowercase(), tag.trim()))
-                            }
+                        state: Some(state), ..
+                    } => {
+                        if let Some(tag_type) = filter_tag_type(filter) {
+                            query.extend(self.query_terms(tag_type, state, false)?);
                         }
                     }
-                    Input::Text {
-                        name,
-                        state: Some(state),
-                        ..
-                    } => query.push(format!("{}:{}", name.to_lowercase(), state.trim())),
-                    Input::Select {
-                        name,
-                        values,
-                        state,
-                        ..
-                    } if name == &SORT_FILTER.name() => {
-                        let state = state.unwrap_or(0);
-                        if let Some(InputType::String(state)) = values.get(state as usize) {
-                            sort = Some(format!("sort={}", state.replace(" ", "-").to_lowercase()));
-                        }
+                    Input::Select { name, state, .. } if name == &SORT_FILTER.name() => {
+                        sort = match state.unwrap_or(0) {
+                            0 => Some(Sort::Popular),
+                            1 => Some(Sort::PopularWeek),
+                            2 => Some(Sort::PopularToday),
+                            _ => Some(Sort::Date),
+                        };
                     }
                     _ => {}
                 }
             }
         }
 
-        let mut query_str = if query.is_empty() {
+        let query = if query.is_empty() {
             r#""""#.to_string()
         } else {
             query.join(" ")
         };
 
-        if let Some(sort) = sort {
-            query_str = format!("{query_str}&{sort}");
+        Ok(request::Search {
+            query,
+            page: page.max(1),
+            sort,
+        })
+    }
+
+    /// Search terms of the comma separated tags of a filter, an unknown tag
+    /// fails the search with suggestions
+    fn query_terms(&self, tag_type: TagType, values: &str, exclude: bool) -> Result<Vec<String>> {
+        split_tags(values, exclude)
+            .into_iter()
+            .map(|(excluded, value)| {
+                let name = self.tag_name(tag_type, &value)?;
+                Ok(tags::query_term(tag_type, &name, excluded))
+            })
+            .collect()
+    }
+
+    /// Search terms of the blacklist preference, which applies to every
+    /// listing, so unknown tags are only logged and used as written
+    fn blacklist_terms(&self, values: &str) -> Vec<String> {
+        split_tags(values, true)
+            .into_iter()
+            .map(|(excluded, value)| {
+                let name = self.tag_name(TagType::Tag, &value).unwrap_or_else(|e| {
+                    error!("blacklisted tag: {e}");
+                    value
+                });
+                tags::query_term(TagType::Tag, &name, excluded)
+            })
+            .collect()
+    }
+
+    /// Name of the tag a filter value refers to, values are only normalised
+    /// when the tag index can not be reached
+    fn tag_name(&self, tag_type: TagType, value: &str) -> Result<String> {
+        match self
+            .tags
+            .lookup(tag_type, value, |name| self.autocomplete(tag_type, name))
+        {
+            Ok(candidates) => tags::validate(tag_type, value, &candidates),
+            Err(e) => {
+                error!("failed to look up {tag_type} \"{value}\": {e}");
+                Ok(tags::normalize(value))
+            }
         }
+    }
+
+    /// Tags of a type with names starting like `name`
+    fn autocomplete(&self, tag_type: TagType, name: &str) -> Result<Vec<Tag>> {
+        let res: AutocompleteResult = self
+            .client
+            .post(&format!("{URL}/api/autocomplete"))
+            .send_form(&[("name", name), ("type", &tag_type.to_string())])?
+            .into_json()?;
 
-        query_str
+        Ok(res.result)
     }
 
-    fn get_manga_list(&self, url: &str) -> Result<Vec<MangaInfo>> {
-        // Send the request and get the response as a string
-        let res = self.client.get(&url).call()?.into_string()?;
+    fn get_manga_list(&self, search: request::Search) -> Result<Vec<MangaInfo>> {
+        let url = format!("{URL}/api/galleries/search?{}", search.to_query_string()?);
+        let res: SearchResult = self.client.get(&url).call()?.into_json()?;
 
-        let document = Html::parse_document(&res);
-        let gallery_selector =
-            Selector::parse(".gallery").map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let thumbnail_selector =
-            Selector::parse("a > img").map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let path_selector =
-            Selector::parse("a").map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let title_selector = Selector::parse("a > .caption")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
+        let title = self.title_kind();
+        Ok(res
+            .result
+            .iter()
+            .map(|gallery| map_gallery_to_manga(gallery, title))
+            .collect())
+    }
 
-        let mut manga_list = vec![];
-        for gallery in document.select(&gallery_selector) {
-            let cover_url = gallery
-                .select(&thumbnail_selector)
-                .flat_map(|thumbnail| thumbnail.value().attr("data-src"))
-                .next()
-                .map(|s| s.to_string())
-                .ok_or_else(|| anyhow!("cover_url not found"))?;
+    /// Client sending the session cookie preference, cookies set by a login
+    /// are kept in `store` as well
+    fn build_client(&self, mut store: CookieStore) -> Agent {
+        if let Some(session) = text(&self.preferences, &SESSION_COOKIE) {
+            if let Err(e) = insert_cookie(&mut store, URL, "sessionid", &session) {
+                error!("failed to set session cookie: {e}");
+            }
+        }
 
-            let path = gallery
-                .select(&path_selector)
-                .flat_map(|link| link.value().attr("href"))
-                .next()
-                .map(|s| s.to_string())
-                .ok_or_else(|| anyhow!("path not found"))?;
+        match &self.solution {
+            Some(solution) => build_flaresolverr_agent(solution, store),
+            None => build_ureq_agent(None, Some(store)),
+        }
+    }
 
-            let title = gallery
-                .select(&title_selector)
-                .flat_map(|caption| caption.text().next())
-                .next()
-                .map(|s| s.to_string())
-                .ok_or_else(|| anyhow!("title not found"))?;
+    fn title_kind(&self) -> TitleKind {
+        match self.preferences.iter().find(|pref| TITLE_SELECT.eq(pref)) {
+            Some(Input::Select { state: Some(1), .. }) => TitleKind::English,
+            Some(Input::Select { state: Some(2), .. }) => TitleKind::Japanese,
+            _ => TitleKind::Pretty,
+        }
+    }
 
-            let manga = MangaInfo {
-                source_id: ID,
-                status: None,
-                title,
-                author: vec![],
-                genre: vec![],
-                description: None,
-                path,
-                cover_url,
-            };
+    fn is_logged_in(&self) -> bool {
+        cookie_value(&self.client, URL, "sessionid").is_some()
+    }
 
-            manga_list.push(manga);
+    /// Log in with the username and password preferences unless a session is
+    /// already there, nhentai may ask for a captcha in which case only the
+    /// session cookie preference works
+    fn login(&self) -> Result<()> {
+        if self.is_logged_in() {
+            return Ok(());
         }
-        Ok(manga_list)
+
+        let (username, password) = match (
+            text(&self.preferences, &USERNAME),
+            text(&self.preferences, &PASSWORD),
+        ) {
+            (Some(username), Some(password)) => (username, password),
+            _ => bail!("log in to nhentai with a session cookie or username and password"),
+        };
+
+        let login_url = format!("{URL}/login/");
+        let html = self.client.get(&login_url).call()?.into_string()?;
+        let token = csrf_token(&html).ok_or_else(|| anyhow!("no csrf token in login form"))?;
+
+        self.client
+            .post(&format!("{login_url}?next=/"))
+            .set("Referer", &login_url)
+            .send_form(&[
+                ("csrfmiddlewaretoken", &token),
+                ("username_or_email", &username),
+                ("password", &password),
+            ])?;
+
+        if !self.is_logged_in() {
+            bail!("nhentai login failed, set the session cookie preference instead");
+        }
+
+        Ok(())
+    }
+
+    /// Galleries of a page of the account favorites
+    fn get_favorites(&self, page: i64, query: Option<String>) -> Result<Vec<MangaInfo>> {
+        self.login()?;
+
+        let favorites = request::Favorites {
+            q: query,
+            page: page.max(1),
+        };
+        let url = format!("{URL}/favorites/?{}", favorites.to_query_string()?);
+        let html = self.client.get(&url).call()?.into_string()?;
+
+        parse_favorites(&html)
+    }
+
+    fn get_gallery(&self, path: &str) -> Result<Gallery> {
+        let id = gallery_id(path).ok_or_else(|| anyhow!("invalid gallery path {path}"))?;
+        let url = format!("{URL}/api/gallery/{id}");
+
+        Ok(self.client.get(&url).call()?.into_json()?)
+    }
+}
+
+/// Id of a `/g/{id}/` path
+fn gallery_id(path: &str) -> Option<i64> {
+    path.trim_start_matches("/g/")
+        .trim_end_matches('/')
+        .parse()
+        .ok()
+}
+
+/// Plain tags, then the parodies, characters, languages and categories with
+/// their type as namespace, e.g. `parody:original`
+fn genre(gallery: &Gallery) -> Vec<String> {
+    let mut genre = gallery.tags_of(TagType::Tag);
+    for tag_type in [
+        TagType::Parody,
+        TagType::Character,
+        TagType::Language,
+        TagType::Category,
+    ] {
+        genre.extend(
+            gallery
+                .tags_of(tag_type)
+                .into_iter()
+                .map(|name| format!("{}:{}", tag_type, name)),
+        );
+    }
+
+    genre
+}
+
+fn map_gallery_to_manga(gallery: &Gallery, title: TitleKind) -> MangaInfo {
+    MangaInfo {
+        source_id: ID,
+        title: gallery
+            .title
+            .get(title)
+            .map(|title| title.to_string())
+            .unwrap_or_else(|| gallery.id.to_string()),
+        author: gallery.tags_of(TagType::Artist),
+        genre: genre(gallery),
+        status: None,
+        description: Some(format!("#{}\nPages: {}", gallery.id, gallery.num_pages)),
+        path: gallery.path(),
+        cover_url: gallery.cover_url(),
+    }
+}
+
+/// A gallery is read as a single chapter, translated by its groups or the
+/// scanlator credited on the gallery
+fn map_gallery_to_chapter(gallery: &Gallery) -> ChapterInfo {
+    let groups = gallery.tags_of(TagType::Group);
+    let scanlator = if groups.is_empty() {
+        Some(gallery.scanlator.trim().to_string()).filter(|scanlator| !scanlator.is_empty())
+    } else {
+        Some(groups.join(", "))
+    };
+
+    ChapterInfo {
+        source_id: ID,
+        title: "Chapter 1".to_string(),
+        path: gallery.path(),
+        number: 1_f64,
+        scanlator,
+        uploaded: gallery.upload_date,
     }
 }
 
 impl Extension for NHentai {
     fn set_preferences(&mut self, preferences: Vec<Input>) -> anyhow::Result<()> {
+        let previous = account(&self.preferences);
         for input in preferences {
             for pref in self.preferences.iter_mut() {
                 if input.eq(pref) {
@@ -246,6 +544,10 @@ impl Extension for NHentai {
             }
         }
 
+        if account(&self.preferences) != previous {
+            self.client = self.build_client(CookieStore::default());
+        }
+
         Ok(())
     }
 
@@ -266,14 +568,11 @@ impl Extension for NHentai {
     }
 
     fn get_popular_manga(&self, page: i64) -> anyhow::Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        self.get_manga_list(&format!(
-            "{URL}/search/?q={}&sort=popular&page={page}",
-            self.query(None)
-        ))
+        self.get_manga_list(self.search_request(page, None, Some(Sort::Popular))?)
     }
 
     fn get_latest_manga(&self, page: i64) -> anyhow::Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        self.get_manga_list(&format!("{URL}/search/?q={}&page={page}", self.query(None)))
+        self.get_manga_list(self.search_request(page, None, Some(Sort::Date))?)
     }
 
     fn search_manga(
@@ -282,203 +581,48 @@ impl Extension for NHentai {
         query: Option<String>,
         filters: Option<Vec<Input>>,
     ) -> anyhow::Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        let url = if filters.is_some() {
-            format!("{URL}/search/?q={}&page={page}", self.query(filters))
+        let favorites = filters.iter().flatten().any(|filter| {
+            FAVORITES_FILTER.eq(filter)
+                && matches!(
+                    filter,
+                    Input::Checkbox {
+                        state: Some(true),
+                        ..
+                    }
+                )
+        });
+        if favorites {
+            return self.get_favorites(page, query);
+        }
+
+        let search = if filters.is_some() {
+            self.search_request(page, filters, None)?
         } else if let Some(query) = query {
-            format!("{URL}/search/?q={query}&sort=popular&page={page}")
+            request::Search {
+                query,
+                page: page.max(1),
+                sort: Some(Sort::Popular),
+            }
         } else {
             return Err(anyhow!("query and filters cannot be both empty"));
         };
 
-        self.get_manga_list(&url)
+        self.get_manga_list(search)
     }
 
     fn get_manga_detail(&self, path: String) -> anyhow::Result<tanoshi_lib::prelude::MangaInfo> {
-        let url = format!("{}{}", URL, path);
-        // Send the request and get the response as a string
-        let res = self.client.get(&url).call()?.into_string()?;
-
-        let document = Html::parse_document(&res);
-        let gallery_id_selector = Selector::parse("h3[id=\"gallery_id\"]")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let parodies_selector = Selector::parse("a[href^=\"/parody/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let characters_selector = Selector::parse("a[href^=\"/character/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let languages_selector = Selector::parse("a[href^=\"/language/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let categories_selector = Selector::parse("a[href^=\"/category/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let pages_selector = Selector::parse("a[href^=\"/search/?q=pages\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let thumbnail_selector = Selector::parse("#cover > a > img")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let title_selector = Selector::parse("h1.title > .pretty")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let author_selector = Selector::parse("a[href^=\"/artist/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let genre_selector = Selector::parse("a[href^=\"/tag/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-
-        let mut description = "".to_string();
-        if let Some(gallery_id) = document.select(&gallery_id_selector).next().map(|el| {
-            el.text()
-                .into_iter()
-                .map(|id| id.to_string())
-                .collect::<Vec<String>>()
-                .join("")
-        }) {
-            description = format!("{}", gallery_id);
-        }
-        let parodies = document
-            .select(&parodies_selector)
-            .into_iter()
-            .filter_map(|el| el.text().next())
-            .collect::<Vec<&str>>()
-            .join(",");
-        if !parodies.is_empty() {
-            description = format!("{}\nParodies: {}", description, parodies);
-        }
-        let characters = document
-            .select(&characters_selector)
-            .into_iter()
-            .filter_map(|el| el.text().next())
-            .collect::<Vec<&str>>()
-            .join(",");
-        if !characters.is_empty() {
-            description = format!("{}\nCharacters: {}", description, characters);
-        }
-        let languages = document
-            .select(&languages_selector)
-            .into_iter()
-            .filter_map(|el| el.text().next())
-            .collect::<Vec<&str>>()
-            .join(",");
-        if !languages.is_empty() {
-            description = format!("{}\nLanguages: {}", description, languages);
-        }
-        let categories = document
-            .select(&categories_selector)
-            .into_iter()
-            .filter_map(|el| el.text().next())
-            .collect::<Vec<&str>>()
-            .join(",");
-        if !categories.is_empty() {
-            description = format!("{}\nCategories: {}", description, categories);
-        }
-        if let Some(pages) = document.select(&pages_selector).next().map(|el| {
-            el.text()
-                .into_iter()
-                .map(|id| id.to_string())
-                .collect::<Vec<String>>()
-                .join("")
-        }) {
-            description = format!("{}\nPages: {}", description, pages);
-        }
-
-        let cover_url = document
-            .select(&thumbnail_selector)
-            .flat_map(|el| el.value().attr("data-src"))
-            .next()
-            .map(|s| s.to_string())
-            .ok_or_else(|| anyhow!("cover not found"))?;
-
-        let title = document
-            .select(&title_selector)
-            .flat_map(|el| el.text())
-            .next()
-            .map(|s| s.to_string())
-            .ok_or_else(|| anyhow!("title not found"))?;
-
-        let author: Vec<String> = document
-            .select(&author_selector)
-            .flat_map(|el| el.text())
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>();
-
-        let genre: Vec<String> = document
-            .select(&genre_selector)
-            .flat_map(|el| el.text())
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>();
-
-        let manga = MangaInfo {
-            source_id: ID,
-            status: None,
-            path,
-            description: Some(description),
-            title,
-            author,
-            genre,
-            cover_url,
-        };
-
-        Ok(manga)
+        Ok(map_gallery_to_manga(
+            &self.get_gallery(&path)?,
+            self.title_kind(),
+        ))
     }
 
     fn get_chapters(&self, path: String) -> anyhow::Result<Vec<tanoshi_lib::prelude::ChapterInfo>> {
-        let url = format!("{}{}", URL, path);
-
-        // Send the request and get the response as a string
-        let res = self.client.get(&url).call()?.into_string()?;
-
-        let document = Html::parse_document(&res);
-        let scanlator_selector = Selector::parse("a[href^=\"/group/\"] > .name")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let uploaded_selector = Selector::parse(".tags > time")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-        let scanlator = document
-            .select(&scanlator_selector)
-            .flat_map(|el| el.text())
-            .next()
-            .map(|s| s.to_string());
-        let uploaded = if let Some(uploaded) = document.select(&uploaded_selector).next() {
-            uploaded
-                .value()
-                .attr("datetime")
-                .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%S%.f%z").ok())
-                .map(|dt| dt.timestamp())
-        } else {
-            None
-        };
-
-        let chapter = ChapterInfo {
-            source_id: ID,
-            title: "Chapter 1".to_string(),
-            path,
-            number: 1_f64,
-            scanlator,
-            uploaded: uploaded.unwrap_or_else(|| 0),
-        };
-
-        Ok(vec![chapter])
+        Ok(vec![map_gallery_to_chapter(&self.get_gallery(&path)?)])
     }
 
     fn get_pages(&self, path: String) -> anyhow::Result<Vec<String>> {
-        let url = format!("{}{}", URL, path);
-
-        // Send the request and get the response as a string
-        let res = self.client.get(&url).call()?.into_string()?;
-
-        let document = Html::parse_document(&res);
-        let page_selector = Selector::parse(".thumb-container > .gallerythumb > img")
-            .map_err(|e| anyhow!("failed to parse selector: {e:?}"))?;
-
-        let mut pages = vec![];
-        let re = Regex::new(r"^https:\/\/t(\d*)\..+\/(\d+)\/(\d+)t.(.+)$")?;
-        for thumb in document.select(&page_selector) {
-            if let Some(url) = thumb.value().attr("data-src") {
-                let cap = re
-                    .captures(url)?
-                    .ok_or(anyhow::anyhow!("no captured regex for {url}"))?;
-                pages.push(format!(
-                    "https://i{}.nhentai.net/galleries/{}/{}.{}",
-                    &cap[1], &cap[2], &cap[3], &cap[4]
-                ));
-            }
-        }
-
-        Ok(pages)
+        Ok(self.get_gallery(&path)?.page_urls())
     }
 
     fn headers(&self) -> std::collections::HashMap<String, String> {
@@ -603,8 +747,239 @@ mod test {
 
         let res = nhentai.get_pages("/g/385965".to_string()).unwrap();
         assert!(!res.is_empty());
-        let re = Regex::new(r"https://i\d*.nhentai.net/galleries/2099700/1.jpg").unwrap();
+        assert_eq!(res[0], "https://i.nhentai.net/galleries/2099700/1.jpg");
+    }
+
+    fn gallery_fixture() -> Gallery {
+        serde_json::from_str(include_str!("../fixtures/gallery.json")).unwrap()
+    }
+
+    #[test]
+    fn test_map_gallery_to_manga() {
+        let manga = map_gallery_to_manga(&gallery_fixture(), TitleKind::Pretty);
+
+        assert_eq!(manga.title, "Lady, Maid ni datsu");
+        assert_eq!(manga.path, "/g/385965");
+        assert_eq!(manga.author, vec!["hinasaki yo"]);
+        assert_eq!(
+            manga.genre,
+            vec![
+                "big breasts",
+                "maid",
+                "x-ray",
+                "parody:original",
+                "language:english",
+                "language:translated",
+                "category:manga"
+            ]
+        );
+        assert_eq!(manga.description.unwrap(), "#385965\nPages: 5");
+    }
+
+    #[test]
+    fn test_title_preference() {
+        let mut nhentai = NHentai::default();
+        assert_eq!(nhentai.title_kind(), TitleKind::Pretty);
+
+        let mut title = TITLE_SELECT.clone();
+        if let Input::Select { state, .. } = &mut title {
+            *state = Some(2);
+        }
+        nhentai.set_preferences(vec![title]).unwrap();
+        assert_eq!(nhentai.title_kind(), TitleKind::Japanese);
+
+        let manga = map_gallery_to_manga(&gallery_fixture(), nhentai.title_kind());
+        assert_eq!(
+            manga.title,
+            "[陽先よ] レディ、メイドに脱ぐ (COMIC 快楽天 2021年12月号) [英訳] [DL版]"
+        );
+    }
+
+    #[test]
+    fn test_map_gallery_to_chapter() {
+        let mut gallery = gallery_fixture();
+        let chapter = map_gallery_to_chapter(&gallery);
+
+        assert_eq!(chapter.path, "/g/385965");
+        assert_eq!(chapter.scanlator.as_deref(), Some("hinasaki"));
+        assert_eq!(chapter.uploaded, 1637589934);
+
+        gallery.tags.clear();
+        gallery.scanlator = "Doujins.com".to_string();
+        let chapter = map_gallery_to_chapter(&gallery);
+        assert_eq!(chapter.scanlator.as_deref(), Some("Doujins.com"));
+    }
+
+    #[test]
+    fn test_gallery_id() {
+        assert_eq!(gallery_id("/g/385965"), Some(385965));
+        assert_eq!(gallery_id("/g/385965/"), Some(385965));
+        assert_eq!(gallery_id("/search/?q=maid"), None);
+    }
+
+    #[test]
+    fn test_csrf_token() {
+        assert_eq!(
+            csrf_token(include_str!("../fixtures/login.html")).unwrap(),
+            "rX0ZqzCk3YQb0nPz6UwTzwQ5OPB3ZMA8rYqJX2mZFJ1Fzq7n8m4rHf2kQ0n0Zt9s"
+        );
+        assert!(csrf_token("<form></form>").is_none());
+    }
+
+    #[test]
+    fn test_parse_favorites() {
+        let favorites = parse_favorites(include_str!("../fixtures/favorites.html")).unwrap();
+        let paths: Vec<&str> = favorites.iter().map(|manga| manga.path.as_str()).collect();
+        assert_eq!(paths, vec!["/g/385965", "/g/177013"]);
+        assert_eq!(
+            favorites[1].title,
+            "[ShindoLA] METAMORPHOSIS (Complete) [English]"
+        );
+        assert_eq!(
+            favorites[1].cover_url,
+            "https://t.nhentai.net/galleries/987560/thumb.gif"
+        );
+        assert_eq!(favorites[1].source_id, ID);
+    }
 
-        assert!(re.is_match(&res[0]).unwrap());
+    #[test]
+    fn test_session_cookie_preference() {
+        let mut nhentai = NHentai::default();
+        assert!(!nhentai.is_logged_in());
+
+        let session = |state: Option<&str>| Input::Text {
+            name: "Session cookie (sessionid)".to_string(),
+            state: state.map(|s| s.to_string()),
+        };
+        nhentai
+            .set_preferences(vec![session(Some(" 0f1e2d3c4b5a "))])
+            .unwrap();
+        assert_eq!(
+            cookie_value(&nhentai.client, URL, "sessionid").as_deref(),
+            Some("0f1e2d3c4b5a")
+        );
+
+        nhentai.set_preferences(vec![session(None)]).unwrap();
+        assert!(!nhentai.is_logged_in());
+    }
+
+    #[ignore = "Needs a logged in session in NHENTAI_SESSION"]
+    #[test]
+    fn test_get_favorites() {
+        let mut nhentai = create_test_instance();
+        nhentai
+            .set_preferences(vec![Input::Text {
+                name: "Session cookie (sessionid)".to_string(),
+                state: env::var("NHENTAI_SESSION").ok(),
+            }])
+            .unwrap();
+
+        let mut filters = nhentai.filter_list();
+        for filter in filters.iter_mut() {
+            if let Input::Checkbox { state, .. } = filter {
+                *state = Some(true);
+            }
+        }
+
+        let res = nhentai.search_manga(1, None, Some(filters)).unwrap();
+        assert!(!res.is_empty());
+    }
+
+    #[test]
+    fn test_search_request() {
+        let nhentai = create_test_instance();
+        let tag = |id: i64, tag_type: &str, name: &str| -> Tag {
+            serde_json::from_value(serde_json::json!({
+                "id": id,
+                "type": tag_type,
+                "name": name,
+                "url": format!("/{}/{}/", tag_type, name.replace(' ', "-")),
+                "count": 1000,
+            }))
+            .unwrap()
+        };
+        let seed = |tag_type: TagType, name: &str, tags: Vec<Tag>| {
+            nhentai.tags.lookup(tag_type, name, |_| Ok(tags)).unwrap();
+        };
+        seed(
+            TagType::Tag,
+            "posession",
+            vec![tag(23895, "tag", "possession")],
+        );
+        seed(TagType::Tag, "x-ray", vec![tag(24201, "tag", "x-ray")]);
+        seed(
+            TagType::Parody,
+            "azur lane",
+            vec![tag(102549, "parody", "azur lane")],
+        );
+
+        let mut filters = nhentai.filter_list();
+        for filter in filters.iter_mut() {
+            if SORT_FILTER.eq(filter) {
+                if let Input::Select { state, .. } = filter {
+                    *state = Some(3);
+                }
+            } else if TAG_FILTER.eq(filter) {
+                if let Input::Text { state, .. } = filter {
+                    *state = Some("-X-Ray".to_string());
+                }
+            } else if PARODIES_FILTER.eq(filter) {
+                if let Input::Text { state, .. } = filter {
+                    *state = Some(" Azur  Lane ".to_string());
+                }
+            }
+        }
+        let search = nhentai
+            .search_request(0, Some(filters.clone()), None)
+            .unwrap();
+        assert_eq!(
+            search.query,
+            "language:english -tag:posession -tag:\"x-ray\" parody:\"azur lane\""
+        );
+        assert_eq!(
+            search.to_query_string().unwrap(),
+            "query=language%3Aenglish+-tag%3Aposession+-tag%3A%22x-ray%22+parody%3A%22azur+lane%22&page=1&sort=date"
+        );
+
+        for filter in filters.iter_mut() {
+            if PARODIES_FILTER.eq(filter) {
+                if let Input::Text { state, .. } = filter {
+                    *state = Some("azur lan".to_string());
+                }
+            }
+        }
+        nhentai
+            .tags
+            .lookup(TagType::Parody, "azur lan", |_| {
+                Ok(vec![tag(102549, "parody", "azur lane")])
+            })
+            .unwrap();
+        let err = nhentai.search_request(1, Some(filters), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown parody \"azur lan\", did you mean \"azur lane\"?"
+        );
+    }
+
+    #[test]
+    fn test_unknown_blacklist_tag() {
+        let nhentai = create_test_instance();
+        let tag: Tag = serde_json::from_value(serde_json::json!({
+            "id": 23895,
+            "type": "tag",
+            "name": "possession",
+            "url": "/tag/possession/",
+        }))
+        .unwrap();
+        nhentai
+            .tags
+            .lookup(TagType::Tag, "posession", |_| Ok(vec![tag]))
+            .unwrap();
+
+        // the misspelled blacklist entry does not break popular or latest
+        let search = nhentai
+            .search_request(1, None, Some(Sort::Popular))
+            .unwrap();
+        assert_eq!(search.query, "language:english -tag:posession");
     }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use crate::dto::{Tag, TagType};
+
+/// Suggestions listed when a filter value is not a known tag
+const SUGGESTIONS: usize = 3;
+
+/// Lowercase name with single spaces and without surrounding quotes, the form
+/// nhentai uses for tag names
+pub fn normalize(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches('"')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Search term of a tag, names with spaces or hyphens are quoted so they are
+/// matched as a whole
+pub fn query_term(tag_type: TagType, name: &str, exclude: bool) -> String {
+    let sign = if exclude { "-" } else { "" };
+    if name.contains(|c: char| c.is_whitespace() || c == '-') {
+        format!("{}{}:\"{}\"", sign, tag_type, name)
+    } else {
+        format!("{}{}:{}", sign, tag_type, name)
+    }
+}
+
+/// Name of the tag among `candidates` matching `value`, an error suggests the
+/// closest candidates otherwise
+pub fn validate(tag_type: TagType, value: &str, candidates: &[Tag]) -> Result<String> {
+    let value = normalize(value);
+    let mut candidates: Vec<&Tag> = candidates
+        .iter()
+        .filter(|tag| tag.tag_type == tag_type)
+        .collect();
+    if let Some(tag) = candidates.iter().find(|tag| tag.name == value) {
+        return Ok(tag.name.clone());
+    }
+
+    candidates.sort_by(|a, b| b.count.cmp(&a.count));
+    let suggestions: Vec<String> = candidates
+        .iter()
+        .take(SUGGESTIONS)
+        .map(|tag| format!("\"{}\"", tag.name))
+        .collect();
+    if suggestions.is_empty() {
+        bail!("unknown {} \"{}\"", tag_type, value);
+    } else {
+        bail!(
+            "unknown {} \"{}\", did you mean {}?",
+            tag_type,
+            value,
+            suggestions.join(", ")
+        );
+    }
+}
+
+/// Tags found for a name and when they were fetched
+type Lookup = (Instant, Vec<Tag>);
+
+/// Tags found by name lookups, kept for `ttl`
+pub struct TagIndex {
+    ttl: Duration,
+    lookups: RwLock<HashMap<(TagType, String), Lookup>>,
+}
+
+impl TagIndex {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            lookups: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Store the tags found for `name`, each tag is also indexed by its own name
+    fn insert(&self, tag_type: TagType, name: &str, tags: Vec<Tag>) {
+        if let Ok(mut lookups) = self.lookups.write() {
+            let now = Instant::now();
+            for tag in tags.iter().filter(|tag| tag.tag_type == tag_type) {
+                lookups.insert((tag_type, tag.name.clone()), (now, vec![tag.clone()]));
+            }
+            lookups.insert((tag_type, normalize(name)), (now, tags));
+        }
+    }
+
+    /// Tags matching `name`, calling `fetch` when the lookup is not cached or
+    /// older than `ttl`
+    pub fn lookup<F>(&self, tag_type: TagType, name: &str, fetch: F) -> Result<Vec<Tag>>
+    where
+        F: FnOnce(&str) -> Result<Vec<Tag>>,
+    {
+        let name = normalize(name);
+        if let Ok(lookups) = self.lookups.read() {
+            if let Some((fetched_at, tags)) = lookups.get(&(tag_type, name.clone())) {
+                if fetched_at.elapsed() < self.ttl {
+                    return Ok(tags.clone());
+                }
+            }
+        }
+
+        let tags = fetch(&name)?;
+        self.insert(tag_type, &name, tags.clone());
+
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::dto::AutocompleteResult;
+
+    fn tags() -> Vec<Tag> {
+        let res: AutocompleteResult =
+            serde_json::from_str(include_str!("../fixtures/autocomplete.json")).unwrap();
+        res.result
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("  Big   Breasts "), "big breasts");
+        assert_eq!(normalize("\"x-ray\""), "x-ray");
+    }
+
+    #[test]
+    fn test_query_term() {
+        assert_eq!(query_term(TagType::Tag, "maid", false), "tag:maid");
+        assert_eq!(
+            query_term(TagType::Tag, "big breasts", true),
+            "-tag:\"big breasts\""
+        );
+        assert_eq!(query_term(TagType::Tag, "x-ray", true), "-tag:\"x-ray\"");
+        assert_eq!(
+            query_term(TagType::Parody, "azur lane", false),
+            "parody:\"azur lane\""
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let tags = tags();
+        assert_eq!(
+            validate(TagType::Tag, "Big Breasts", &tags).unwrap(),
+            "big breasts"
+        );
+        assert_eq!(
+            validate(TagType::Tag, "big breast", &tags)
+                .unwrap_err()
+                .to_string(),
+            "unknown tag \"big breast\", did you mean \"big breasts\", \"big ass\", \"big penis\"?"
+        );
+        assert_eq!(
+            validate(TagType::Group, "big breasts", &tags)
+                .unwrap_err()
+                .to_string(),
+            "unknown group \"big breasts\""
+        );
+    }
+
+    #[test]
+    fn test_tag_index() {
+        let index = TagIndex::new(Duration::from_secs(60));
+        let fetches = Cell::new(0);
+        let fetch = |name: &str| {
+            assert_eq!(name, "big breast");
+            fetches.set(fetches.get() + 1);
+            Ok(tags())
+        };
+
+        assert_eq!(
+            index
+                .lookup(TagType::Tag, "Big Breast", fetch)
+                .unwrap()
+                .len(),
+            5
+        );
+        assert_eq!(
+            index
+                .lookup(TagType::Tag, "big breast", fetch)
+                .unwrap()
+                .len(),
+            5
+        );
+        assert_eq!(fetches.get(), 1);
+
+        // tags in a lookup are known by their own names
+        let found = index
+            .lookup(TagType::Tag, "big breasts", |_| {
+                panic!("tag should be indexed")
+            })
+            .unwrap();
+        assert_eq!(found[0].name, "big breasts");
+
+        let index = TagIndex::new(Duration::ZERO);
+        index
+            .lookup(TagType::Tag, "big breast", |_| Ok(tags()))
+            .unwrap();
+        assert!(index
+            .lookup(TagType::Tag, "big breast", |_| Err(anyhow!("offline")))
+            .is_err());
+    }
+}
@@ -12,7 +12,7 @@ fn register(registrar: &mut dyn PluginRegistrar) {
 }
 
 lazy_static! {
-    static ref PREFERENCES: Vec<Input> = vec![];
+    static ref PREFERENCES: Vec<Input> = nepnep::get_preferences();
 }
 
 const ID: i64 = 3;
@@ -33,6 +33,12 @@ impl Default for Weebcentral {
     }
 }
 
+impl Weebcentral {
+    fn cover_url(&self) -> Option<String> {
+        nepnep::get_cover_url(&self.preferences)
+    }
+}
+
 impl Extension for Weebcentral {
     fn set_preferences(&mut self, preferences: Vec<Input>) -> Result<()> {
         for input in preferences {
@@ -67,11 +73,11 @@ impl Extension for Weebcentral {
     }
 
     fn get_popular_manga(&self, page: i64) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        nepnep::get_popular_manga(ID, URL, page, &self.client)
+        nepnep::get_popular_manga(ID, URL, page, self.cover_url().as_deref(), &self.client)
     }
 
     fn get_latest_manga(&self, page: i64) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        nepnep::get_latest_manga(ID, URL, page, &self.client)
+        nepnep::get_latest_manga(ID, URL, page, self.cover_url().as_deref(), &self.client)
     }
 
     fn search_manga(
@@ -80,11 +86,19 @@ impl Extension for Weebcentral {
         query: Option<String>,
         filters: Option<Vec<Input>>,
     ) -> Result<Vec<tanoshi_lib::prelude::MangaInfo>> {
-        nepnep::search_manga(ID, URL, page, query, filters, &self.client)
+        nepnep::search_manga(
+            ID,
+            URL,
+            page,
+            query,
+            filters,
+            self.cover_url().as_deref(),
+            &self.client,
+        )
     }
 
     fn get_manga_detail(&self, path: String) -> Result<tanoshi_lib::prelude::MangaInfo> {
-        nepnep::get_manga_detail(ID, URL, path, &self.client)
+        nepnep::get_manga_detail(ID, URL, path, self.cover_url().as_deref(), &self.client)
     }
 
     fn get_chapters(&self, path: String) -> Result<Vec<tanoshi_lib::prelude::ChapterInfo>> {